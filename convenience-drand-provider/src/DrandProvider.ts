@@ -38,6 +38,7 @@ export class DrandProvider {
     }
 
     lastPendingTime = 0
+    sentRounds = new Set<number>()
     secondsToWait: number = 6;
     private drandClient: HttpChainClient
     inputSender: InputSender
@@ -56,9 +57,14 @@ export class DrandProvider {
             const res = await this.inspectAxiosInstance.get<PendingDrandBeacon>('/pendingdrandbeacon')
 
             if (Array.isArray(res.data.reports) && res.data.reports.length > 0) {
-                const firstReport = res.data.reports.at(0);
-                if (firstReport?.payload && firstReport.payload !== '0x00') {
-                    return { inputTime: Number(firstReport.payload) }
+                // the first report is the input time, the others are rounds timelocks are sealed until
+                const [firstReport, ...roundReports] = res.data.reports
+                const rounds = roundReports
+                    .map(report => Number(report.payload ?? 0))
+                    .filter(round => round > 0)
+                const inputTime = Number(firstReport?.payload ?? 0)
+                if (inputTime > 0 || rounds.length > 0) {
+                    return { inputTime, rounds }
                 }
             }
         } catch (error) {
@@ -96,6 +102,9 @@ export class DrandProvider {
         while (this.desiredState === 'RUNNING') {
             try {
                 const pending = await this.pendingDrandBeacon()
+                if (pending) {
+                    await this.sendPendingRounds(pending.rounds)
+                }
                 if (this.canSendBeacon(pending)) {
                     const beacon = await fetchBeacon(this.drandClient)
                     console.log('sending beacon', beacon.round)
@@ -109,11 +118,30 @@ export class DrandProvider {
         }
     }
 
+    /**
+     * Timelocks only open with the beacon of their exact round, the latest beacon is not enough
+     */
+    private async sendPendingRounds(rounds: number[]) {
+        const toSend = rounds.filter(round => !this.sentRounds.has(round))
+        if (toSend.length === 0) {
+            return
+        }
+        const info = await this.drandClient.chain().info()
+        const latestRound = Math.floor((Date.now() / 1000 - info.genesis_time) / info.period) + 1
+        for (const round of toSend.filter(round => round <= latestRound)) {
+            const beacon = await fetchBeacon(this.drandClient, round)
+            console.log('sending beacon of pending round', beacon.round)
+            this.inputSender.sendInput({ payload: JSON.stringify({ beacon }) })
+            this.sentRounds.add(round)
+        }
+    }
+
     private canSendBeacon(
         pending: Awaited<ReturnType<typeof this.pendingDrandBeacon>>
     ): pending is NonNullable<typeof pending> {
         return (
             (pending &&
+                pending.inputTime > 0 &&
                 this.lastPendingTime !== pending.inputTime &&
                 pending.inputTime < Date.now() / 1000 - this.secondsToWait) ??
             false
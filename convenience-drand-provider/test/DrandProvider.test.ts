@@ -16,6 +16,13 @@ describe('DrandProvider', () => {
             const resp = await provider.pendingDrandBeacon()
             expect(resp?.inputTime).toBeDefined()
         })
+        it("should inform the rounds sealed timelocks are waiting for", async () => {
+            Helper.nockInspectEndpointRoundIsNeeded()
+            const provider = new DrandProvider()
+            const resp = await provider.pendingDrandBeacon()
+            expect(resp?.inputTime).toBe(0)
+            expect(resp?.rounds).toEqual([1000])
+        })
         it("should respond null when inspect response is 0x00, aka no need for beacon", async () => {
             Helper.nockInspectEndpointRandomIsntNeeded()
             const provider = new DrandProvider()
@@ -18,6 +18,22 @@ export default class Helper {
                 "processed_input_count": 0
             })
     }
+    static nockInspectEndpointRoundIsNeeded() {
+        return nock(Helper.nockUrl)
+            .get(/\/inspect\/pendingdrandbeacon/)
+            .reply(200, {
+                "status": "Accepted",
+                "reports": [
+                    {
+                        "payload": "0x0"
+                    },
+                    {
+                        "payload": "0x00000000000003e8"
+                    }
+                ],
+                "processed_input_count": 0
+            })
+    }
     static nockInspectEndpointRandomIsntNeeded() {
         return nock(Helper.nockUrl)
             .get(/\/inspect\/pendingdrandbeacon/)
@@ -0,0 +1,52 @@
+use cartesi_drand::{
+    drand::{get_drand_beacon, PRIMARY_CHAIN},
+    models::structs::{DrandChain, DrandScheme},
+    utils::util::generate_payload_hex,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::json;
+
+/**
+ * The quicknet chain the beacons below were signed on
+ */
+fn quicknet() -> Vec<DrandChain> {
+    vec![DrandChain {
+        name: PRIMARY_CHAIN.to_string(),
+        scheme: DrandScheme::default(),
+        public_key: "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a".to_string(),
+        period: 3,
+        genesis_time: 1692803367,
+    }]
+}
+
+/**
+ * Verification cost per beacon input on the host, run with `cargo bench`
+ */
+fn bench_get_drand_beacon(c: &mut Criterion) {
+    let chains = quicknet();
+    let mut group = c.benchmark_group("get_drand_beacon");
+
+    // a signature of another round never passes, so every input runs the pairing check
+    let invalid = generate_payload_hex(
+        json!({"beacon":{"round":4088011,"randomness":"","signature":"b75a01a468f49abde3b5c81c0713d819845d113bb5a66bd3a576fe40b10927271d9d25c1c1bbf66b73e7b3c2b6399648"}}),
+    )
+    .unwrap();
+    group.bench_function("uncached", |b| {
+        b.iter(|| get_drand_beacon(black_box(&invalid), &chains).unwrap_err())
+    });
+
+    // after the first verification the round comes from the cache
+    let valid = generate_payload_hex(
+        json!({"beacon":{"round":2797373,"randomness":"","signature":"857325b9d4d9e81b32ff98f0da6fc2c6af02b10207ef148d2d39b82b77159d766a9edf8a68a793135890a7dfa66166a7"}}),
+    )
+    .unwrap();
+    get_drand_beacon(&valid, &chains).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| get_drand_beacon(black_box(&valid), &chains).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_get_drand_beacon);
+criterion_main!(benches);
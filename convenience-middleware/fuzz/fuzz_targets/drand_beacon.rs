@@ -0,0 +1,38 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use libfuzzer_sys::fuzz_target;
+
+use cartesi_drand::{
+    drand::{self, PRIMARY_CHAIN},
+    models::structs::{DrandChain, DrandScheme},
+};
+
+// A fixed quicknet chain keeps the target deterministic
+fn chains() -> &'static [DrandChain] {
+    static CHAINS: OnceLock<Vec<DrandChain>> = OnceLock::new();
+    CHAINS.get_or_init(|| {
+        vec![DrandChain {
+            name: PRIMARY_CHAIN.to_string(),
+            scheme: DrandScheme::default(),
+            public_key: "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a".to_string(),
+            period: 3,
+            genesis_time: 1692803367,
+        }]
+    })
+}
+
+fuzz_target!(|data: &[u8]| {
+    // raw bytes as the hex payload of an advance, with and without the ABI selector
+    let _ = drand::get_drand_beacon(&format!("0x{}", hex::encode(data)), chains());
+
+    let mut abi = drand::beacon_selector().to_vec();
+    abi.extend_from_slice(data);
+    let _ = drand::get_drand_beacon(&format!("0x{}", hex::encode(abi)), chains());
+
+    // and as the payload string itself
+    if let Ok(payload) = std::str::from_utf8(data) {
+        let _ = drand::get_drand_beacon(payload, chains());
+    }
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use cartesi_drand::{
+    drand,
+    rollup::input::{has_input_inside_input, RollupInput},
+    tlock,
+};
+
+fn check(input: &RollupInput) {
+    let _ = has_input_inside_input(input);
+    let _ = input.decoded_inspect();
+    let _ = drand::is_querying_pending_beacon(input);
+    let _ = drand::get_drand_config_update(input);
+    let _ = tlock::get_timelock(&input.data.payload);
+}
+
+fuzz_target!(|data: &[u8]| {
+    // a whole request from the rollup server
+    if let Ok(input) = serde_json::from_slice::<RollupInput>(data) {
+        check(&input);
+    }
+
+    // or only its payload
+    let mut input = RollupInput::default();
+    input.data.payload = format!("0x{}", hex::encode(data));
+    check(&input);
+});
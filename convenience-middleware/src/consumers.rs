@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use dotenvy::var;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::{
+    models::structs::{Consumer, ConsumerConfig, ConsumerRoute, InputBufferManager},
+    rollup::input::RollupInput,
+};
+
+pub const DEFAULT_CONSUMER: &str = "default";
+
+/**
+ * The default consumer owns the given manager and answers /finish and /random,
+ * the others come from CONSUMERS as a JSON list and answer /consumers/{name}/finish and /consumers/{name}/random
+ *
+ * [{"name":"blackjack"},{"name":"poker","prefix":"0x01"}]
+ */
+pub fn load_consumers(manager: Arc<Mutex<InputBufferManager>>) -> Vec<Consumer> {
+    let configs = match var("CONSUMERS") {
+        Ok(consumers) => {
+            serde_json::from_str::<Vec<ConsumerConfig>>(&consumers).unwrap_or_else(|e| {
+                error!("Invalid env CONSUMERS: {}", e);
+                vec![]
+            })
+        }
+        Err(_) => vec![],
+    };
+
+    let default = Consumer {
+        name: DEFAULT_CONSUMER.to_string(),
+        prefix: None,
+        input_buffer_manager: manager,
+    };
+
+    let mut consumers = vec![default];
+    for config in configs {
+        if consumers
+            .iter()
+            .any(|consumer| consumer.name == config.name)
+        {
+            error!("Consumer {} registered twice, ignoring", config.name);
+            continue;
+        }
+        consumers.push(Consumer {
+            name: config.name,
+            prefix: config
+                .prefix
+                .map(|prefix| normalize_hex(&prefix))
+                .filter(|prefix| !prefix.is_empty()),
+            input_buffer_manager: Arc::new(Mutex::new(InputBufferManager::default())),
+        });
+    }
+    consumers
+}
+
+fn normalize_hex(value: &str) -> String {
+    value.trim_start_matches("0x").to_lowercase()
+}
+
+/**
+ * Name of the consumer an input belongs to, the default consumer when nothing matches
+ */
+pub fn route_input<'a>(
+    consumers: &'a [Consumer],
+    route: &ConsumerRoute,
+    input: &RollupInput,
+) -> &'a str {
+    let payload = normalize_hex(&input.data.payload);
+
+    let consumer = match route {
+        ConsumerRoute::Field(field) => {
+            let name = hex::decode(&payload)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+                .and_then(|value| value.get(field)?.as_str().map(str::to_string));
+            name.and_then(|name| consumers.iter().find(|consumer| consumer.name == name))
+        }
+        // the longest prefix wins, so "0x01" and "0x0102" can both be registered
+        ConsumerRoute::Prefix => consumers
+            .iter()
+            .filter_map(|consumer| Some((consumer, consumer.prefix.as_ref()?)))
+            .filter(|(_, prefix)| payload.starts_with(prefix.as_str()))
+            .max_by_key(|(_, prefix)| prefix.len())
+            .map(|(consumer, _)| consumer),
+    };
+
+    consumer.map_or(DEFAULT_CONSUMER, |consumer| consumer.name.as_str())
+}
@@ -0,0 +1,255 @@
+use std::str::FromStr;
+
+use dotenvy::var;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Sha3_256};
+use tracing::warn;
+
+/**
+ * HKDF-SHA256 can expand up to 255 blocks of 32 bytes
+ */
+pub const MAX_OUTPUTS: usize = 255;
+
+pub const OUTPUT_SIZE: usize = 32;
+
+pub const HKDF_SALT: &[u8] = b"cartesi-drand";
+
+pub const REQUEST_PREFIX: &[u8] = b"randomness_request:";
+
+/**
+ * Versioned function from the beacons picked by the chain policy to the randomness served by /random.
+ * A released version never changes, see "Randomness derivation" in the README for the spec and vectors.
+ */
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum RandomnessDerivation {
+    #[default]
+    #[serde(rename = "sha3-v1")]
+    Sha3V1,
+    #[serde(rename = "sha256-v1")]
+    Sha256V1,
+    #[serde(rename = "blake3-v1")]
+    Blake3V1,
+    #[serde(rename = "hkdf-sha256-v1")]
+    HkdfSha256V1,
+}
+
+impl FromStr for RandomnessDerivation {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "sha3-v1" => Ok(RandomnessDerivation::Sha3V1),
+            "sha256-v1" => Ok(RandomnessDerivation::Sha256V1),
+            "blake3-v1" => Ok(RandomnessDerivation::Blake3V1),
+            "hkdf-sha256-v1" => Ok(RandomnessDerivation::HkdfSha256V1),
+            _ => Err(format!("Unknown randomness derivation {}", value)),
+        }
+    }
+}
+
+impl RandomnessDerivation {
+    /**
+     * RANDOMNESS_DERIVATION env, sha3-v1 when unset
+     */
+    pub fn from_env() -> RandomnessDerivation {
+        match var("RANDOMNESS_DERIVATION") {
+            Ok(value) => value.parse().unwrap_or_else(|e| {
+                warn!("{}, using sha3-v1 derivation", e);
+                RandomnessDerivation::default()
+            }),
+            Err(_) => RandomnessDerivation::default(),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RandomnessDerivation::Sha3V1 => "sha3-v1",
+            RandomnessDerivation::Sha256V1 => "sha256-v1",
+            RandomnessDerivation::Blake3V1 => "blake3-v1",
+            RandomnessDerivation::HkdfSha256V1 => "hkdf-sha256-v1",
+        }
+    }
+
+    /**
+     * Only HKDF expands to more than one output
+     */
+    pub fn supports_outputs(&self, outputs: usize) -> bool {
+        match self {
+            RandomnessDerivation::HkdfSha256V1 => (1..=MAX_OUTPUTS).contains(&outputs),
+            _ => outputs == 1,
+        }
+    }
+
+    /**
+     * Derive `outputs` blocks of 32 bytes from the hex randomness of the beacons, in chain order,
+     * and the counter of randomness served so far
+     */
+    pub fn derive(&self, randomness: &[&str], counter: u64, outputs: usize) -> Option<Vec<u8>> {
+        if !self.supports_outputs(outputs) {
+            return None;
+        }
+
+        let counter_be = counter.to_be_bytes();
+        let output = match self {
+            RandomnessDerivation::Sha3V1 => sha3_v1(randomness, counter),
+            RandomnessDerivation::Sha256V1 => {
+                <Sha256 as sha2::Digest>::digest(&[&hex_ikm(randomness)?[..], &counter_be].concat())
+                    .to_vec()
+            }
+            RandomnessDerivation::Blake3V1 => {
+                blake3::hash(&[&hex_ikm(randomness)?[..], &counter_be].concat())
+                    .as_bytes()
+                    .to_vec()
+            }
+            RandomnessDerivation::HkdfSha256V1 => {
+                let mut okm = vec![0u8; OUTPUT_SIZE * outputs];
+                Hkdf::<Sha256>::new(Some(HKDF_SALT), &hex_ikm(randomness)?)
+                    .expand(&counter_be, &mut okm)
+                    .ok()?;
+                okm
+            }
+        };
+        Some(output)
+    }
+}
+
+/**
+ * Hex input appended after the beacons for a named consumer, so consumers never share a seed
+ */
+pub fn consumer_namespace(name: &str) -> String {
+    hex::encode(<Sha256 as sha2::Digest>::digest(name.as_bytes()))
+}
+
+/**
+ * Hex input appended after the beacons for a randomness request, so requests never share
+ * a value with each other or with /random
+ */
+pub fn request_namespace(id: &str) -> String {
+    hex::encode(<Sha256 as sha2::Digest>::digest(
+        &[REQUEST_PREFIX, id.as_bytes()].concat(),
+    ))
+}
+
+/**
+ * Bytes of the hex randomness of the beacons, in chain order
+ */
+fn hex_ikm(randomness: &[&str]) -> Option<Vec<u8>> {
+    randomness
+        .iter()
+        .map(hex::decode)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .map(|ikm| ikm.concat())
+}
+
+/**
+ * The original construction, hashing hex strings and a little endian counter
+ */
+fn sha3_v1(randomness: &[&str], counter: u64) -> Vec<u8> {
+    let seed = match randomness {
+        [randomness] => randomness.to_string(),
+        _ => {
+            let mut hasher = Sha3_256::new();
+            randomness
+                .iter()
+                .for_each(|randomness| hasher.update(randomness.as_bytes()));
+            hex::encode(hasher.finalize())
+        }
+    };
+
+    let mut hasher = Sha3_256::new();
+    hasher.update([seed.as_bytes(), &counter.to_le_bytes()].concat());
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{request_namespace, RandomnessDerivation};
+
+    const PRIMARY: &str = "7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527";
+    const FALLBACK: &str = "a8482088c159d7a5c9a54cf599c686febbef00d97d0c460dfee3cd80ff371dd9";
+
+    /**
+     * Same vectors as the README, computed with Python's hashlib/hmac and the BLAKE3 reference
+     */
+    const VECTORS: [(RandomnessDerivation, &[&str], u64, usize, &str); 9] = [
+        (RandomnessDerivation::Sha3V1, &[PRIMARY], 1, 1, "a0e68303b27400e78fd3170af2a5387f9a8fe291545f8461cafafd90fb0e7357"),
+        (RandomnessDerivation::Sha3V1, &[PRIMARY, FALLBACK], 2, 1, "14445e0df8dbbece95c5b70e2223248764da8504eaf9ee3cfd1b8dd2602b47b5"),
+        (RandomnessDerivation::Sha256V1, &[PRIMARY], 1, 1, "e03db3e741d537f10f8424396c78787c015fcb258e9c2facfe03510fa599a26c"),
+        (RandomnessDerivation::Sha256V1, &[PRIMARY, FALLBACK], 2, 1, "490d0b0dbe8f5b509d577fd72b4918bb52cd440695e09b836adc9b22c8b20a8f"),
+        (RandomnessDerivation::Blake3V1, &[PRIMARY], 1, 1, "1ac084df2328c891ef6b5905fec764ddd9184dba30bbf609e71c25d464bedda4"),
+        (RandomnessDerivation::Blake3V1, &[PRIMARY, FALLBACK], 2, 1, "8986ab0ddf394290a1b9694390fc334d065197d1fbb6f0e16dd64a8abe3c6faf"),
+        (RandomnessDerivation::HkdfSha256V1, &[PRIMARY], 1, 1, "cb60e64b4976582cbf3666637e3031872bd0a6d97addd5f5c9246c203b9337d6"),
+        (RandomnessDerivation::HkdfSha256V1, &[PRIMARY, FALLBACK], 2, 2, "f963ab8986ec9d829575057f19454c8ca85b9d8cf31436112664fef8991b6b4fff2d4b4be4b40acf19189221a557c848233870a3619151a0038da500495ef8db"),
+        (RandomnessDerivation::HkdfSha256V1, &[PRIMARY, FALLBACK], 2, 1, "f963ab8986ec9d829575057f19454c8ca85b9d8cf31436112664fef8991b6b4f"),
+    ];
+
+    #[test]
+    fn test_derivation_vectors() {
+        for (derivation, randomness, counter, outputs, expected) in VECTORS {
+            let value = derivation.derive(randomness, counter, outputs).unwrap();
+            assert_eq!(hex::encode(value), expected, "{}", derivation.as_str());
+        }
+    }
+
+    #[test]
+    fn test_request_vectors() {
+        let namespace = request_namespace("table-1");
+        assert_eq!(
+            namespace,
+            "eda7ab8e87e99092c97628ca08b347e10676b6d0aa8ddcb4b5bc7fc0df4d7b0f"
+        );
+
+        let seed = [PRIMARY, namespace.as_str()];
+        for (derivation, expected) in [
+            (
+                RandomnessDerivation::Sha3V1,
+                "178aca5cabed22d22a6e71f71f8fb090d1ae4334f8ead4bd8ecc541bec461dc9",
+            ),
+            (
+                RandomnessDerivation::Sha256V1,
+                "5ac9167fbee8e5a8f7bb1fc7944eb6a592873be2603dda0dd5ac4ae28eca89f2",
+            ),
+        ] {
+            let value = derivation.derive(&seed, 2832127, 1).unwrap();
+            assert_eq!(hex::encode(value), expected, "{}", derivation.as_str());
+        }
+    }
+
+    #[test]
+    fn test_derivation_from_str() {
+        for derivation in VECTORS.map(|(derivation, ..)| derivation) {
+            assert_eq!(derivation.as_str().parse(), Ok(derivation));
+            assert_eq!(
+                serde_json::to_value(derivation).unwrap(),
+                derivation.as_str()
+            );
+        }
+        assert_eq!(
+            RandomnessDerivation::default(),
+            RandomnessDerivation::Sha3V1
+        );
+        assert!("sha3".parse::<RandomnessDerivation>().is_err());
+    }
+
+    #[test]
+    fn test_derivation_outputs() {
+        let hkdf = RandomnessDerivation::HkdfSha256V1;
+        assert_eq!(hkdf.derive(&[PRIMARY], 1, 255).unwrap().len(), 255 * 32);
+        assert!(hkdf.derive(&[PRIMARY], 1, 256).is_none());
+        assert!(hkdf.derive(&[PRIMARY], 1, 0).is_none());
+        assert!(RandomnessDerivation::Sha256V1
+            .derive(&[PRIMARY], 1, 2)
+            .is_none());
+
+        // only sha3-v1 accepts randomness that isn't hex
+        assert!(RandomnessDerivation::Sha3V1
+            .derive(&["mainnet-randomness"], 1, 1)
+            .is_some());
+        assert!(RandomnessDerivation::Blake3V1
+            .derive(&["mainnet-randomness"], 1, 1)
+            .is_none());
+    }
+}
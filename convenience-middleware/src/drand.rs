@@ -1,14 +1,26 @@
-use std::{borrow::BorrowMut, error::Error};
+use std::{
+    borrow::BorrowMut,
+    collections::{BTreeSet, HashSet, VecDeque},
+    error::Error,
+    sync::{Mutex, OnceLock},
+};
 
-use actix_web::web::Data;
 use dotenvy::var;
-use drand_verify::{derive_randomness, G2PubkeyRfc, Pubkey};
-use log::{error, warn};
+use drand_verify::{derive_randomness, G1Pubkey, G2PubkeyFastnet, G2PubkeyRfc, Pubkey};
 use serde_json::json;
+use sha3::{Digest, Keccak256, Sha3_256};
+use tracing::{error, info, warn};
 
 use crate::{
-    models::structs::{AppState, DrandBeacon, PayloadWithBeacon},
-    rollup::{input::RollupInput, server::send_report},
+    models::structs::{
+        AppState, DrandBeacon, DrandChain, DrandEnv, DrandScheme, PayloadWithBeacon,
+        PayloadWithDrandConfig,
+    },
+    rollup::{
+        input::RollupInput,
+        server::{send_notice, send_report},
+    },
+    utils::util::{generate_payload_hex, store_drand_config},
 };
 
 pub fn is_querying_pending_beacon(rollup_input: &RollupInput) -> Result<bool, Box<dyn Error>> {
@@ -16,38 +28,312 @@ pub fn is_querying_pending_beacon(rollup_input: &RollupInput) -> Result<bool, Bo
     Ok(result == "pendingdrandbeacon")
 }
 
-pub async fn send_pending_beacon_report(app_state: &Data<AppState>) {
-    let manager = app_state.input_buffer_manager.lock().await;
-    let x = manager.pending_beacon_timestamp.get();
-    let report = json!({ "payload": format!("{x:#x}") });
-    let _ = send_report(report).await.unwrap();
+/**
+ * Report the latest timestamp any consumer is waiting a beacon for,
+ * then one report per round a sealed timelock is waiting for, as 8 bytes big endian.
+ * The latest beacon is enough for the timestamp, the relayer has to fetch those exact rounds.
+ */
+pub async fn send_pending_beacon_report(app_state: &AppState) {
+    let mut x = 0;
+    let mut rounds = BTreeSet::new();
+    for consumer in &app_state.consumers {
+        let manager = consumer.input_buffer_manager.lock().await;
+        x = x.max(manager.pending_beacon_timestamp.get());
+        rounds.extend(manager.pending_timelock_rounds());
+    }
+    let payloads = std::iter::once(format!("{x:#x}")).chain(
+        rounds
+            .iter()
+            .map(|round| format!("0x{}", hex::encode(round.to_be_bytes()))),
+    );
+    for payload in payloads {
+        if let Err(e) = send_report(json!({ "payload": payload })).await {
+            error!("Error sending pending beacon report: {}", e);
+        }
+    }
+}
+
+const VERIFIED_ROUNDS_CAPACITY: usize = 1024;
+
+/**
+ * Rounds whose signature already passed the pairing check, keyed by round and hash(public key || signature).
+ * Relayers resend the same beacon and inputs are replayed, so the pairing runs once per beacon.
+ */
+#[derive(Default)]
+struct VerifiedRounds {
+    keys: HashSet<(u64, [u8; 32])>,
+    order: VecDeque<(u64, [u8; 32])>,
+}
+
+fn verified_rounds() -> &'static Mutex<VerifiedRounds> {
+    static VERIFIED_ROUNDS: OnceLock<Mutex<VerifiedRounds>> = OnceLock::new();
+    VERIFIED_ROUNDS.get_or_init(Default::default)
+}
+
+fn verified_round_key(round: u64, public_key: &str, signature: &[u8]) -> (u64, [u8; 32]) {
+    let mut hasher = Sha3_256::new();
+    hasher.update(public_key.as_bytes());
+    hasher.update(signature);
+    (round, hasher.finalize().into())
+}
+
+pub fn is_verified_round(round: u64, public_key: &str, signature: &[u8]) -> bool {
+    let key = verified_round_key(round, public_key, signature);
+    verified_rounds()
+        .lock()
+        .map(|cache| cache.keys.contains(&key))
+        .unwrap_or(false)
+}
+
+fn remember_verified_round(round: u64, public_key: &str, signature: &[u8]) {
+    let key = verified_round_key(round, public_key, signature);
+    if let Ok(mut cache) = verified_rounds().lock() {
+        if !cache.keys.insert(key) {
+            return;
+        }
+        cache.order.push_back(key);
+        if cache.order.len() > VERIFIED_ROUNDS_CAPACITY {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.keys.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub const PRIMARY_CHAIN: &str = "primary";
+
+/**
+ * The primary chain comes from DRAND_PUBLIC_KEY, DRAND_PERIOD and DRAND_GENESIS_TIME,
+ * fallback chains from DRAND_CHAINS as a JSON list
+ *
+ * [{"name":"mainnet","scheme":"pedersen-bls-chained","public_key":"868f005e...","period":30,"genesis_time":1595431050}]
+ */
+pub fn load_drand_chains() -> Vec<DrandChain> {
+    let primary = DrandChain {
+        name: PRIMARY_CHAIN.to_string(),
+        scheme: DrandScheme::default(),
+        public_key: var("DRAND_PUBLIC_KEY").expect("Public Key not found"),
+        period: var("DRAND_PERIOD")
+            .expect("Missing env DRAND_PERIOD")
+            .parse()
+            .expect("Invalid env DRAND_PERIOD"),
+        genesis_time: var("DRAND_GENESIS_TIME")
+            .expect("Missing env DRAND_GENESIS_TIME")
+            .parse()
+            .expect("Invalid env DRAND_GENESIS_TIME"),
+    };
+
+    let fallbacks = match var("DRAND_CHAINS") {
+        Ok(chains) => serde_json::from_str::<Vec<DrandChain>>(&chains).unwrap_or_else(|e| {
+            error!("Invalid env DRAND_CHAINS: {}", e);
+            vec![]
+        }),
+        Err(_) => vec![],
+    };
+
+    std::iter::once(primary)
+        .chain(
+            fallbacks
+                .into_iter()
+                .filter(|chain| chain.name != PRIMARY_CHAIN),
+        )
+        .collect()
+}
+
+fn verify_beacon(
+    chain: &DrandChain,
+    beacon: &DrandBeacon,
+    signature: &[u8],
+) -> Result<bool, Box<dyn Error>> {
+    let public_key = hex::decode(&chain.public_key)?;
+    let previous_signature = match (&chain.scheme, &beacon.previous_signature) {
+        (DrandScheme::PedersenChained, Some(previous)) => hex::decode(previous)?,
+        (DrandScheme::PedersenChained, None) => {
+            return Err(format!("Chain {} needs the previous signature", chain.name).into())
+        }
+        _ => vec![],
+    };
+    let round = beacon.round;
+
+    let valid = match chain.scheme {
+        DrandScheme::UnchainedOnG1Rfc9380 => G2PubkeyRfc::from_variable(&public_key)
+            .map_err(|e| e.to_string())?
+            .verify(round, b"", signature)?,
+        DrandScheme::UnchainedOnG1 => G2PubkeyFastnet::from_variable(&public_key)
+            .map_err(|e| e.to_string())?
+            .verify(round, b"", signature)?,
+        DrandScheme::PedersenChained | DrandScheme::PedersenUnchained => {
+            G1Pubkey::from_variable(&public_key)
+                .map_err(|e| e.to_string())?
+                .verify(round, &previous_signature, signature)?
+        }
+    };
+    Ok(valid)
 }
 
 /**
- * Check if the request is a drand beacon
+ * Check if the input is a drand config update sent by the owner (DRAND_CONFIG_OWNER env)
+ * Example of a drand config update
+ *
+ * {"drand_config_update":{"DRAND_PUBLIC_KEY":"83cf0f28...","DRAND_PERIOD":3,"DRAND_GENESIS_TIME":1692803367,"DRAND_SAFE_SECONDS":5}}
+ */
+pub fn get_drand_config_update(rollup_input: &RollupInput) -> Result<DrandEnv, Box<dyn Error>> {
+    let payload = rollup_input.decoded_inspect()?;
+    let payload = serde_json::from_str::<PayloadWithDrandConfig>(&payload)?;
+
+    let owner = var("DRAND_CONFIG_OWNER").or(Err("Drand config owner not defined"))?;
+    let sender = rollup_input
+        .data
+        .metadata
+        .as_ref()
+        .map(|metadata| metadata.msg_sender.as_str())
+        .ok_or("Missing input metadata")?;
+
+    let normalize = |address: &str| address.trim_start_matches("0x").to_lowercase();
+    if normalize(sender) != normalize(&owner) {
+        warn!("Drand config update from {} is not the owner", sender);
+        return Err("Invalid drand config owner".into());
+    }
+
+    let mut pk = [0u8; 96];
+    hex::decode_to_slice(
+        &payload.drand_config_update.DRAND_PUBLIC_KEY,
+        pk.borrow_mut(),
+    )?;
+    G2PubkeyRfc::from_fixed(pk).map_err(|e| e.to_string())?;
+    if payload.drand_config_update.DRAND_PERIOD == Some(0) {
+        return Err("DRAND_PERIOD must be positive".into());
+    }
+
+    Ok(payload.drand_config_update)
+}
+
+/**
+ * Apply the drand config update to the running state, persist it and record it with a notice
+ */
+pub async fn apply_drand_config_update(
+    app_state: &AppState,
+    rollup_input: &RollupInput,
+    drand: DrandEnv,
+) {
+    let notice = json!({
+        "drand_config_updated": &drand,
+        "input_index": rollup_input.data.metadata.as_ref().map(|metadata| metadata.input_index),
+    });
+
+    if let Err(e) = store_drand_config(drand.clone()).await {
+        error!("Error updating drand config: {}", e);
+        return;
+    }
+    app_state.update_drand_config(&drand);
+    info!("Drand config updated by the owner");
+
+    let notice = match generate_payload_hex(notice) {
+        Ok(payload) => json!({ "payload": payload }),
+        Err(e) => {
+            error!("Error encoding drand config notice: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = send_notice(notice).await {
+        error!("Error sending drand config notice: {}", e);
+    }
+}
+
+/**
+ * Solidity signature of the ABI encoded beacon, the payload is prefixed by its selector
+ */
+pub const BEACON_ABI_SIGNATURE: &str = "drandBeacon(uint64,bytes)";
+
+pub fn beacon_selector() -> [u8; 4] {
+    let hash = Keccak256::digest(BEACON_ABI_SIGNATURE.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/**
+ * Decode abi.encodeWithSignature("drandBeacon(uint64,bytes)", round, signature)
+ */
+fn decode_abi_beacon(payload: &[u8]) -> Result<PayloadWithBeacon, Box<dyn Error>> {
+    const WORD: usize = 32;
+
+    let data = payload
+        .strip_prefix(&beacon_selector())
+        .ok_or("Missing beacon selector")?;
+
+    let word = |index: usize| -> Result<&[u8], Box<dyn Error>> {
+        let start = index.checked_mul(WORD).ok_or("ABI offset too large")?;
+        Ok(data
+            .get(start..start.checked_add(WORD).ok_or("ABI offset too large")?)
+            .ok_or("ABI payload too short")?)
+    };
+    let as_usize = |word: &[u8]| -> Result<usize, Box<dyn Error>> {
+        if word[..WORD - 8].iter().any(|&b| b != 0) {
+            return Err("ABI value out of range".into());
+        }
+        Ok(u64::from_be_bytes(word[WORD - 8..].try_into()?).try_into()?)
+    };
+
+    let round = as_usize(word(0)?)? as u64;
+    let offset = as_usize(word(1)?)?;
+    if offset % WORD != 0 {
+        return Err("Invalid ABI bytes offset".into());
+    }
+    let length = as_usize(word(offset / WORD)?)?;
+    let start = offset.checked_add(WORD).ok_or("ABI offset too large")?;
+    let signature = data
+        .get(start..start.checked_add(length).ok_or("ABI bytes too long")?)
+        .ok_or("ABI payload too short")?;
+
+    Ok(PayloadWithBeacon {
+        beacon: DrandBeacon {
+            round,
+            signature: hex::encode(signature),
+            randomness: String::new(),
+            ..Default::default()
+        },
+    })
+}
+
+/**
+ * Check if the request is a drand beacon, either ABI encoded with the beacon selector or JSON
  * Example of a drand beacon request
  *
  * {"beacon":{"round":3828300,"randomness":"7ff726d290836da706126ada89f7e99295c672d6768ec8e035fd3de5f3f35cd9","signature":"ab85c071a4addb83589d0ecf5e2389f7054e4c34e0cbca65c11abc30761f29a0d338d0d307e6ebcb03d86f781bc202ee"}}
  */
-pub fn get_drand_beacon(payload: &str) -> Result<DrandBeacon, Box<dyn std::error::Error>> {
-    let key = var("DRAND_PUBLIC_KEY").expect("Public Key not found");
-
+pub fn get_drand_beacon(
+    payload: &str,
+    chains: &[DrandChain],
+) -> Result<DrandBeacon, Box<dyn std::error::Error>> {
     let payload = payload.trim_start_matches("0x");
     let payload = hex::decode(payload)?;
-    let payload = std::str::from_utf8(&payload).map(|s| s.to_owned())?;
 
-    let payload = serde_json::from_str::<PayloadWithBeacon>(&payload)?;
+    let payload = if payload.starts_with(&beacon_selector()) {
+        decode_abi_beacon(&payload)?
+    } else {
+        let payload = std::str::from_utf8(&payload).map(|s| s.to_owned())?;
+        serde_json::from_str::<PayloadWithBeacon>(&payload)?
+    };
 
-    let mut pk = [0u8; 96];
-    hex::decode_to_slice(&key, pk.borrow_mut())?;
-
-    let pk = G2PubkeyRfc::from_fixed(pk).map_err(|e| e.to_string())?;
+    let name = payload.beacon.chain.as_deref().unwrap_or(PRIMARY_CHAIN);
+    let chain = chains
+        .iter()
+        .find(|chain| chain.name == name)
+        .ok_or_else(|| format!("Unknown drand chain {}", name))?;
+    let key = &chain.public_key;
 
     let signature = hex::decode(&payload.beacon.signature)?;
 
     let round = payload.beacon.round;
 
-    match pk.verify(round, b"", &signature) {
+    let mut beacon = payload.beacon.to_owned();
+    beacon.chain = Some(chain.name.clone());
+
+    if is_verified_round(round, key, &signature) {
+        beacon.randomness = hex::encode(derive_randomness(&signature));
+        return Ok(beacon);
+    }
+
+    match verify_beacon(chain, &payload.beacon, &signature) {
         Ok(valid) => {
             if !valid {
                 let msg = format!(
@@ -58,15 +344,15 @@ pub fn get_drand_beacon(payload: &str) -> Result<DrandBeacon, Box<dyn std::error
                 warn!("{msg}");
                 return Err(msg.into());
             }
-            let mut beacon = payload.beacon.to_owned();
+            remember_verified_round(round, key, &signature);
 
             // make sure that the signature is the source of randomness
             beacon.randomness = hex::encode(derive_randomness(&signature));
             Ok(beacon)
         }
         Err(e) => {
-            error!("Drand VerificationError: {}", e.to_string());
-            Err(Box::new(e))
+            error!("Drand VerificationError: {}", e);
+            Err(e)
         }
     }
 }
@@ -1,12 +1,16 @@
+use std::borrow::Cow;
+
 use actix_web::{error, http::header::ContentType, HttpResponse};
 use derive_more::{Display, Error};
+use utoipa::{
+    openapi::{schema::Type, ObjectBuilder, RefOr, Schema},
+    PartialSchema, ToSchema,
+};
 
 #[derive(Debug, Display, Error)]
 pub enum CheckerError {
     #[display(fmt = "Error updating drand config: {}", cause)]
-    InvalidDrandConfig {
-        cause: String,
-    },
+    InvalidDrandConfig { cause: String },
 
     #[display(fmt = "Already inspecting")]
     AlreadyInspecting,
@@ -30,7 +34,37 @@ pub enum CheckerError {
     RandomnessError,
 
     #[display(fmt = "Error storing input")]
-    StoreInputError
+    StoreInputError,
+
+    #[display(fmt = "No input being processed to bind the randomness request")]
+    NoInputForRandomnessRequest,
+
+    #[display(fmt = "Randomness request already registered")]
+    DuplicatedRandomnessRequest,
+
+    #[display(fmt = "Randomness request not found")]
+    UnknownRandomnessRequest,
+
+    #[display(fmt = "Randomness request waiting for round {}", round)]
+    PendingRandomnessRequest { round: u64 },
+
+    #[display(fmt = "Drand config endpoint is disabled")]
+    DrandConfigEndpointDisabled,
+
+    #[display(fmt = "Timelock not found")]
+    UnknownTimelock,
+
+    #[display(fmt = "Timelock sealed until round {}", round)]
+    SealedTimelock { round: u64 },
+
+    #[display(fmt = "Consumer not found")]
+    UnknownConsumer,
+
+    #[display(fmt = "Derivation {} cannot produce {} outputs", derivation, outputs)]
+    UnsupportedRandomnessOutputs {
+        derivation: &'static str,
+        outputs: usize,
+    },
 }
 
 impl serde::Serialize for CheckerError {
@@ -47,6 +81,24 @@ impl serde::Serialize for CheckerError {
     }
 }
 
+/**
+ * Same shape as the Serialize impl, a string holding {"error": message}
+ */
+impl PartialSchema for CheckerError {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("JSON encoded {\"error\": message}"))
+            .into()
+    }
+}
+
+impl ToSchema for CheckerError {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("Error")
+    }
+}
+
 impl error::ResponseError for CheckerError {
     fn status_code(&self) -> hyper::StatusCode {
         match *self {
@@ -59,6 +111,15 @@ impl error::ResponseError for CheckerError {
             CheckerError::SignatureErrorBeacon => hyper::StatusCode::BAD_REQUEST,
             CheckerError::RandomnessError => hyper::StatusCode::BAD_REQUEST,
             CheckerError::StoreInputError => hyper::StatusCode::BAD_REQUEST,
+            CheckerError::NoInputForRandomnessRequest => hyper::StatusCode::BAD_REQUEST,
+            CheckerError::DuplicatedRandomnessRequest => hyper::StatusCode::BAD_REQUEST,
+            CheckerError::UnknownRandomnessRequest => hyper::StatusCode::NOT_FOUND,
+            CheckerError::PendingRandomnessRequest { .. } => hyper::StatusCode::BAD_REQUEST,
+            CheckerError::DrandConfigEndpointDisabled => hyper::StatusCode::FORBIDDEN,
+            CheckerError::UnknownTimelock => hyper::StatusCode::NOT_FOUND,
+            CheckerError::SealedTimelock { .. } => hyper::StatusCode::BAD_REQUEST,
+            CheckerError::UnknownConsumer => hyper::StatusCode::NOT_FOUND,
+            CheckerError::UnsupportedRandomnessOutputs { .. } => hyper::StatusCode::BAD_REQUEST,
         }
     }
 
@@ -0,0 +1,13 @@
+pub mod consumers;
+pub mod derivation;
+pub mod drand;
+pub mod errors;
+mod main_test;
+pub mod models;
+pub mod openapi;
+pub mod recorder;
+pub mod rollup;
+pub mod router;
+pub mod telemetry;
+pub mod tlock;
+pub mod utils;
@@ -1,43 +1,75 @@
-mod drand;
-mod errors;
-mod main_test;
-mod models;
-mod rollup;
-mod router;
-mod utils;
-
-use crate::models::structs::AppState;
-use crate::router::routes;
-use crate::utils::util::load_env_from_json;
-use actix_web::{middleware::Logger, web, App, HttpServer};
-use log::info;
+use actix_web::{
+    middleware::{from_fn, Logger},
+    web, App, HttpServer,
+};
+use cartesi_drand::models::structs::{AppState, RunMode};
+use cartesi_drand::recorder::record_dapp_calls;
+use cartesi_drand::router::routes;
+use cartesi_drand::telemetry::{init_tracing, trace_dapp_calls};
+use cartesi_drand::utils::util::{
+    load_env_from_json, shutdown_signal, take_snapshot, write_snapshot,
+};
+use tracing::{error, info};
+use utoipa_actix_web::AppExt;
+
+/**
+ * Seconds the in-flight DApp calls have to finish after SIGTERM or SIGINT
+ */
+const SHUTDOWN_TIMEOUT: u64 = 30;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     load_env_from_json().await.unwrap();
 
-    let env = env_logger::Env::default().default_filter_or("info");
-    env_logger::builder()
-        .parse_env(env)
-        .format_timestamp(None)
-        .try_init()
-        .unwrap();
+    init_tracing();
 
     let app_state = web::Data::new(AppState::new());
 
+    // only the host keeps state across restarts, the Cartesi Machine rebuilds it from the inputs
+    let keeps_snapshot = app_state.mode == RunMode::Host;
+    if keeps_snapshot {
+        match take_snapshot().await {
+            Ok(snapshot) => app_state.restore(snapshot).await,
+            Err(e) => info!("No snapshot restored: {}", e),
+        }
+    }
+
     info!("Starting server");
 
-    HttpServer::new(move || {
+    let server_state = app_state.clone();
+    let server = HttpServer::new(move || {
         let logger = Logger::default();
 
         App::new()
+            .wrap(from_fn(record_dapp_calls))
             .wrap(logger)
-            .app_data(app_state.clone())
-            .service(routes::request_random)
-            .service(routes::consume_buffer)
-            .service(routes::update_drand_config)
+            .wrap(from_fn(trace_dapp_calls))
+            .app_data(server_state.clone())
+            .into_utoipa_app()
+            .configure(routes::configure)
+            .into_app()
     })
     .bind(("0.0.0.0", 8080))?
-    .run()
-    .await
+    .disable_signals()
+    .shutdown_timeout(SHUTDOWN_TIMEOUT)
+    .run();
+
+    let handle = server.handle();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = shutdown_signal().await {
+            error!("Cannot listen to shutdown signals: {}", e);
+            return;
+        }
+        info!("Shutting down, draining in-flight requests");
+        handle.stop(true).await;
+    });
+
+    server.await?;
+
+    if keeps_snapshot {
+        if let Err(e) = write_snapshot(&app_state.snapshot().await).await {
+            error!("Error writing snapshot: {}", e);
+        }
+    }
+    Ok(())
 }
@@ -4,27 +4,40 @@ mod middleware_tests {
     use std::{error::Error, sync::Once};
 
     use crate::{
-        drand::get_drand_beacon,
-        models::structs::{AppState, Beacon, DrandBeacon},
+        derivation::RandomnessDerivation,
+        drand::{
+            beacon_selector, get_drand_beacon, get_drand_config_update, is_verified_round,
+            load_drand_chains,
+        },
+        models::structs::{
+            AppState, Beacon, Consumer, DrandBeacon, DrandEnv, RandomnessRequest,
+            RandomnessRequestId, RequestRollups, RunMode, TimelockCiphertext, Timestamp,
+        },
+        openapi::openapi_document,
+        recorder::{record_dapp_calls, Interaction},
+        rollup::input::{RollupInput, RollupInputDataMetadata, RollupState},
         router::routes::{self},
+        telemetry::{init_tracing, trace_dapp_calls, CORRELATION_ID_HEADER},
+        tlock::test::{QUICKNET_CIPHERTEXT, QUICKNET_ROUND, QUICKNET_SIGNATURE},
         utils::util::{generate_payload_hex, load_env_from_json},
     };
     use actix_web::{
         http::{self},
-        middleware::Logger,
+        middleware::{from_fn, Logger},
         test,
         web::{self},
         App,
     };
     use dotenvy::dotenv;
-    use drand_verify::{G2Pubkey, G2PubkeyRfc, Pubkey as _};
+    use drand_verify::{G2PubkeyFastnet, G2PubkeyRfc, Pubkey as _};
     use http::Method;
     use httptest::{
         matchers::*,
         responders::{self, *},
         Expectation, ServerPool,
     };
-    use serde_json::json;
+    use serde_json::{json, Value};
+    use std::collections::BTreeSet;
 
     #[macro_export]
     macro_rules! check_if_dotenv_is_loaded {
@@ -36,6 +49,7 @@ mod middleware_tests {
 
     static SERVER: ServerPool = ServerPool::new(1);
     static BIND_SERVER: Once = Once::new();
+    static LOAD_ENV: Once = Once::new();
     static BIND_LOGGER: Once = Once::new();
 
     #[macro_export]
@@ -86,15 +100,7 @@ mod middleware_tests {
     }
 
     fn generate_log() -> Logger {
-        BIND_LOGGER.call_once(|| {
-            let env = env_logger::Env::default().default_filter_or("info");
-            env_logger::builder()
-                .parse_env(env)
-                .format_timestamp(None)
-                .is_test(true)
-                .try_init()
-                .unwrap();
-        });
+        BIND_LOGGER.call_once(init_tracing);
 
         Logger::default()
     }
@@ -102,6 +108,7 @@ mod middleware_tests {
     // ({"data":{"metadata":{"block_number":241,"epoch_index":0,"input_index":0,"msg_sender":"0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266","timestamp":1689949250},"payload":payload_empty},"request_type":"advance_state"})
     fn mock_factory(
         payload: Option<serde_json::Value>,
+        input_index: u128,
     ) -> Result<serde_json::Value, Box<dyn Error>> {
         let empty_str = String::from("0x7b22696e707574223a2230783030227d");
 
@@ -109,7 +116,21 @@ mod middleware_tests {
             .map(|p| generate_payload_hex(p).unwrap())
             .unwrap_or(empty_str);
 
-        let json = json!({"data":{"metadata":{"block_number":241,"epoch_index":0,"input_index":0,"msg_sender":"0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266","timestamp":1689949250},"payload": payload},"request_type":"advance_state"});
+        let metadata = RollupInputDataMetadata::builder()
+            .with_block_number(241)
+            .with_epoch_index(0)
+            .with_input_index(input_index)
+            .with_address_sender("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266".to_string())
+            .with_timestamp(1689949250)
+            .build();
+
+        let input = RollupInput::builder()
+            .with_payload(payload)
+            .with_metadata(metadata)
+            .with_request_type(RollupState::Advance)
+            .build();
+
+        let json = serde_json::to_value(input)?;
 
         println!("mock_factory: {}", json);
         Ok(json)
@@ -146,10 +167,35 @@ mod middleware_tests {
         assert_eq!(resp.status(), 400);
         assert_eq!(
             manager.lock().await.pending_beacon_timestamp.get(),
-            timestamp + app_state.safe_seconds
+            timestamp + app_state.safe_seconds()
         );
     }
 
+    #[actix_web::test]
+    async fn request_random_while_inspecting() {
+        check_if_dotenv_is_loaded!();
+
+        let mut app_state = AppState::new();
+        app_state.mode = RunMode::Test;
+        let app_state = web::Data::new(app_state);
+        app_state.set_inspecting(true).await;
+
+        let logger = generate_log();
+        let app = App::new()
+            .wrap(logger)
+            .app_data(app_state.clone())
+            .service(routes::request_random);
+
+        let app = test::init_service(app).await;
+
+        // the rollup server is never called while an inspect is being handled
+        let req = test::TestRequest::with_uri("/random?timestamp=10").to_request();
+        let resp = test::call_and_read_body(&app, req).await;
+        assert!(std::str::from_utf8(&resp)
+            .unwrap()
+            .contains("Already inspecting"));
+    }
+
     #[actix_web::test]
     async fn request_random_with_new_beacon() {
         check_if_dotenv_is_loaded!();
@@ -221,11 +267,188 @@ mod middleware_tests {
         let status = resp.status();
         assert!(status.is_success(), "status: {:?}", status.as_str());
         assert_eq!(status, 200);
+        assert_eq!(
+            resp.headers().get(routes::DRAND_CHAINS_HEADER).unwrap(),
+            "primary"
+        );
 
         assert!(!manager.lock().await.flag_to_hold.is_holding());
         assert!(manager.lock().await.last_beacon.get_mut().is_some());
     }
 
+    #[actix_web::test]
+    async fn request_random_with_derivation_outputs() {
+        check_if_dotenv_is_loaded!();
+
+        let beacon = Beacon::builder()
+            .with_round(1)
+            .with_randomness(
+                "7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527".to_string(),
+            )
+            .with_timestamp(24)
+            .build();
+
+        let mut app_state = AppState::new();
+        assert_eq!(app_state.derivation, RandomnessDerivation::Sha3V1);
+        app_state.derivation = RandomnessDerivation::HkdfSha256V1;
+        let app_state = web::Data::new(app_state);
+        app_state
+            .input_buffer_manager
+            .lock()
+            .await
+            .last_beacon
+            .set(Some(beacon));
+
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::request_random);
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::with_uri("/random?timestamp=14&outputs=256").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let req = test::TestRequest::with_uri("/random?timestamp=14&outputs=2").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers()
+                .get(routes::RANDOMNESS_DERIVATION_HEADER)
+                .unwrap(),
+            "hkdf-sha256-v1"
+        );
+        let body = test::read_body(resp).await;
+        let randomness = std::str::from_utf8(&body).unwrap();
+
+        // HKDF outputs are prefixes of each other, the first one is the single output vector
+        assert_eq!(randomness.len(), 128);
+        assert!(randomness
+            .starts_with("cb60e64b4976582cbf3666637e3031872bd0a6d97addd5f5c9246c203b9337d6"));
+    }
+
+    #[actix_web::test]
+    async fn test_correlation_id_header_follows_current_input() {
+        check_if_dotenv_is_loaded!();
+
+        let payload = mock_factory(None, 3).unwrap();
+        mock_rollup_server!(json_encoded(payload));
+
+        let beacon = Beacon::builder()
+            .with_round(1)
+            .with_randomness("to-be-a-seed".to_string())
+            .with_timestamp(24)
+            .build();
+
+        let app_state = web::Data::new(AppState::new());
+
+        let logger = generate_log();
+        let app = App::new()
+            .wrap(logger)
+            .wrap(from_fn(trace_dapp_calls))
+            .app_data(app_state.clone())
+            .service(routes::consume_buffer)
+            .service(routes::request_random);
+
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get(CORRELATION_ID_HEADER).unwrap(), "0-3");
+
+        app_state
+            .input_buffer_manager
+            .lock()
+            .await
+            .last_beacon
+            .set(Some(beacon));
+
+        let req = test::TestRequest::with_uri("/random?timestamp=14").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get(CORRELATION_ID_HEADER).unwrap(), "0-3");
+    }
+
+    #[actix_web::test]
+    async fn test_consumer_finish_and_random() {
+        check_if_dotenv_is_loaded!();
+
+        let payload = mock_factory(Some(json!({"consumer": "poker", "input": "0x01"})), 4).unwrap();
+        mock_rollup_server!(json_encoded(payload));
+
+        let mut app_state = AppState::new();
+        app_state.consumers.push(Consumer {
+            name: "poker".to_string(),
+            prefix: None,
+            input_buffer_manager: Default::default(),
+        });
+        let app_state = web::Data::new(app_state);
+
+        let app = App::new()
+            .wrap(from_fn(trace_dapp_calls))
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::consume_buffer)
+            .service(routes::consume_consumer_buffer)
+            .service(routes::request_random)
+            .service(routes::request_consumer_random);
+        let app = test::init_service(app).await;
+
+        // the default consumer fetches the input and queues it for poker
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+
+        let req = test::TestRequest::with_uri("/consumers/poker/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get(CORRELATION_ID_HEADER).unwrap(), "0-4");
+        let input: Value = test::read_body_json(resp).await;
+        assert_eq!(input["data"]["metadata"]["input_index"], 4);
+        assert!(app_state.current_input().await.is_none());
+
+        let req = test::TestRequest::with_uri("/consumers/blackjack/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+
+        // the beacon reaches both consumers, the seeds differ
+        let beacon = DrandBeacon::builder()
+            .with_randomness(
+                "7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527".to_string(),
+            )
+            .with_round(2832127)
+            .build();
+        app_state.keep_newest_beacon(beacon, None).await;
+
+        let req = test::TestRequest::with_uri("/random?timestamp=1689949250").to_request();
+        let default_seed = test::call_and_read_body(&app, req).await;
+        let req = test::TestRequest::with_uri("/consumers/poker/random?timestamp=1689949250")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers().get(CORRELATION_ID_HEADER).unwrap(), "0-4");
+        let poker_seed = test::read_body(resp).await;
+
+        assert_eq!(
+            default_seed,
+            "a0e68303b27400e78fd3170af2a5387f9a8fe291545f8461cafafd90fb0e7357"
+        );
+        assert_ne!(default_seed, poker_seed);
+    }
+
     #[actix_web::test]
     async fn test_request_finish_without_input_to_respond() {
         check_if_dotenv_is_loaded!();
@@ -255,7 +478,7 @@ mod middleware_tests {
     async fn test_request_finish_with_input_to_respond() {
         check_if_dotenv_is_loaded!();
 
-        let payload = mock_factory(None).unwrap();
+        let payload = mock_factory(None, 0).unwrap();
 
         mock_rollup_server!(json_encoded(payload));
 
@@ -274,9 +497,34 @@ mod middleware_tests {
         assert_eq!(req["request_type"], "advance_state");
     }
 
+    #[actix_web::test]
+    async fn test_request_finish_with_undecodable_inspect() {
+        check_if_dotenv_is_loaded!();
+
+        let inspect = RollupInput::builder()
+            .with_payload("0xff".to_string())
+            .with_request_type(RollupState::Inspect)
+            .build();
+        mock_rollup_server!(json_encoded(inspect));
+
+        let app_state = web::Data::new(AppState::new());
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::consume_buffer);
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+    }
+
     #[actix_web::test]
     async fn test_request_finish_with_beacon_inside_input() {
-        let empty = mock_factory(None).unwrap();
+        let empty = mock_factory(None, 0).unwrap();
 
         let randomness =
             String::from("7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527");
@@ -286,10 +534,10 @@ mod middleware_tests {
             .with_randomness(randomness)
             .with_round(2832127)
             .with_signature(signature)
-            .build()
-            .wrap();
+            .build();
 
-        let beacon = mock_factory(Some(beacon)).unwrap();
+        // only the beacon, nothing to dispatch to the DApp
+        let beacon = mock_factory(Some(json!({ "beacon": beacon })), 1).unwrap();
 
         check_if_dotenv_is_loaded!();
         mock_rollup_server!(responders::cycle![
@@ -312,9 +560,13 @@ mod middleware_tests {
         let req = call_finish!(&mut app);
         assert_eq!(req["request_type"], "advance_state");
 
-        // call again and the beacon arrives
-        let req = call_finish!(&mut app);
-        assert_eq!(req["request_type"], "advance_state");
+        // call again and the beacon arrives in a later input
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
 
         // check randomness
         let randomness = call_random!(&mut app);
@@ -327,7 +579,7 @@ mod middleware_tests {
     #[actix_web::test]
     async fn test_request_finish_with_beacon_inside_input_scenario_2() {
         check_if_dotenv_is_loaded!();
-        let empty = mock_factory(None).unwrap();
+        let empty = mock_factory(None, 0).unwrap();
 
         let beacon = DrandBeacon::builder()
             .with_randomness("7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527".to_string())
@@ -336,7 +588,7 @@ mod middleware_tests {
             .build()
             .wrap();
 
-        let beacon = mock_factory(Some(beacon)).unwrap();
+        let beacon = mock_factory(Some(beacon), 1).unwrap();
 
         mock_rollup_server!(responders::cycle![
             json_encoded(empty),
@@ -368,6 +620,234 @@ mod middleware_tests {
         );
     }
 
+    #[actix_web::test]
+    async fn test_randomness_request_fulfilled_by_target_round() {
+        check_if_dotenv_is_loaded!();
+
+        let beacon = DrandBeacon::builder()
+            .with_randomness("7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527".to_string())
+            .with_round(2832127)
+            .with_signature("8f4c029827e0c1d6f5db875c1927bc79cb15188e046de5ad627cb7d1efce87b1f3de99a045b770632333a41af3abf352".to_string())
+            .build();
+
+        let beacon = mock_factory(Some(json!({ "beacon": beacon })), 1).unwrap();
+
+        mock_rollup_server!(json_encoded(beacon));
+
+        let app_state = web::Data::new(AppState::new());
+        let logger = generate_log();
+
+        // the DApp is processing the input 0 where the bet was placed
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(0)
+            .with_timestamp(1701299740)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app_state.set_current_input(&input).await;
+
+        let app = App::new()
+            .wrap(logger)
+            .app_data(app_state.clone())
+            .service(routes::consume_buffer)
+            .service(routes::register_randomness_request)
+            .service(routes::fulfil_randomness_request);
+
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::with_uri("/randomness_request")
+            .method(Method::POST)
+            .set_json(json!({"id": "table-1"}))
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["round"], 2832127);
+        assert_eq!(resp["input_index"], 0);
+
+        let req = test::TestRequest::with_uri("/randomness_request/table-1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        // the beacon of the target round arrives in a later input
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 202);
+
+        let req = test::TestRequest::with_uri("/randomness_request/table-1").to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["round"], 2832127);
+        assert_eq!(
+            resp["randomness"],
+            "178aca5cabed22d22a6e71f71f8fb090d1ae4334f8ead4bd8ecc541bec461dc9"
+        );
+
+        let req = test::TestRequest::with_uri("/randomness_request/table-2").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_consumer_randomness_request() {
+        check_if_dotenv_is_loaded!();
+
+        let mut app_state = AppState::new();
+        app_state.consumers.push(Consumer {
+            name: "poker".to_string(),
+            prefix: None,
+            input_buffer_manager: Default::default(),
+        });
+        let app_state = web::Data::new(app_state);
+
+        // poker is processing the input 0 where the bet was placed
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(0)
+            .with_timestamp(1701299740)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        let poker = app_state.for_consumer("poker").unwrap();
+        poker.set_current_input(&input).await;
+
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::fulfil_randomness_request)
+            .service(routes::register_consumer_randomness_request)
+            .service(routes::fulfil_consumer_randomness_request);
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::with_uri("/consumers/poker/randomness_request")
+            .method(Method::POST)
+            .set_json(json!({"id": "table-1"}))
+            .to_request();
+        let resp: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["round"], 2832127);
+
+        let req = test::TestRequest::with_uri("/consumers/dice/randomness_request")
+            .method(Method::POST)
+            .set_json(json!({"id": "table-1"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+
+        let req =
+            test::TestRequest::with_uri("/consumers/poker/randomness_request/table-1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let later = RollupInputDataMetadata::builder()
+            .with_input_index(1)
+            .build();
+        let beacon = DrandBeacon::builder()
+            .with_randomness(
+                "7ade997ac926a8cada6835a4a16dfb2d31e639c7ac4ea4b508d5d3829496b527".to_string(),
+            )
+            .with_round(2832127)
+            .build();
+        app_state.keep_newest_beacon(beacon, Some(&later)).await;
+
+        // the consumer name is mixed in, another consumer with the same id gets another value
+        let req =
+            test::TestRequest::with_uri("/consumers/poker/randomness_request/table-1").to_request();
+        let resp: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["randomness"],
+            "c6a351b551ff7fdadf2b1c303f45f34b8a52b4d96d5601ccbaf8b22de2d08627"
+        );
+
+        // the request belongs to poker only
+        let req = test::TestRequest::with_uri("/randomness_request/table-1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_timelock_opened_by_reported_round() {
+        check_if_dotenv_is_loaded!();
+
+        let timelock = json!({"timelock": {"id": "bid-1", "ciphertext": QUICKNET_CIPHERTEXT}});
+        let timelock = mock_factory(Some(timelock), 1).unwrap();
+
+        let inspect = RollupInput::builder()
+            .with_payload(format!("0x{}", hex::encode("pendingdrandbeacon")))
+            .with_request_type(RollupState::Inspect)
+            .build();
+        let inspect = serde_json::to_value(inspect).unwrap();
+
+        // what the relayer sends after fetching the reported round with drand-client
+        let beacon = json!({"beacon": {
+            "round": QUICKNET_ROUND,
+            "randomness": "fe290beca10872ef2fb164d2aa4442de4566183ec51c56ff3cd603d930e54fdd",
+            "signature": QUICKNET_SIGNATURE,
+        }});
+        let beacon = mock_factory(Some(beacon), 2).unwrap();
+
+        let server = SERVER.get_server();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/finish"))
+                .times(3)
+                .respond_with(cycle![
+                    json_encoded(timelock),
+                    json_encoded(inspect),
+                    json_encoded(beacon)
+                ]),
+        );
+        let round_report = json!({"payload": "0x00000000000003e8"});
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/report"),
+                request::body(json_decoded(eq(round_report.clone()))),
+            ])
+            .times(1)
+            .respond_with(status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/report"),
+                request::body(json_decoded(not(eq(round_report)))),
+            ])
+            .times(1)
+            .respond_with(status_code(200)),
+        );
+        BIND_SERVER.call_once(|| {
+            let url = server.url_str("");
+            let url = url.trim_end_matches("/");
+            std::env::set_var("ROLLUP_HTTP_SERVER_URL", url);
+        });
+
+        let app_state = web::Data::new(AppState::new());
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::consume_buffer)
+            .service(routes::open_timelock);
+        let app = test::init_service(app).await;
+
+        let finish = || {
+            test::TestRequest::with_uri("/finish")
+                .method(Method::POST)
+                .set_json(json!({"status": "accept"}))
+                .to_request()
+        };
+        let opened = || test::TestRequest::with_uri("/timelock/bid-1").to_request();
+
+        // the timelock arrives and stays sealed
+        assert_eq!(test::call_service(&app, finish()).await.status(), 202);
+        assert_eq!(test::call_service(&app, opened()).await.status(), 400);
+
+        // the relayer asks which beacons are pending and learns the round
+        assert_eq!(test::call_service(&app, finish()).await.status(), 202);
+
+        // the beacon of that round opens it
+        assert_eq!(test::call_service(&app, finish()).await.status(), 202);
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, opened()).await;
+        assert_eq!(resp["round"], QUICKNET_ROUND);
+        assert_eq!(
+            resp["plaintext"],
+            format!("0x{}", hex::encode("sealed bid: 42"))
+        );
+    }
+
     #[actix_web::test]
     async fn test_get_drand_beacon() {
         generate_log();
@@ -375,22 +855,448 @@ mod middleware_tests {
         let payload = generate_payload_hex(
             json!({"beacon":{"round":2797373,"randomness":"a8482088c159d7a5c9a54cf599c686febbef00d97d0c460dfee3cd80ff371dd9","signature":"857325b9d4d9e81b32ff98f0da6fc2c6af02b10207ef148d2d39b82b77159d766a9edf8a68a793135890a7dfa66166a7"}}),
         ).unwrap();
-        let beacon = get_drand_beacon(&payload).ok();
+        let chains = load_drand_chains();
+        let beacon = get_drand_beacon(&payload, &chains).ok();
         assert!(beacon.is_some());
 
         let payload = generate_payload_hex(
             json!({"beacon":{"round":4088012,"randomness":"9f020c15bbee97470e2cebe5f600b66cccf600b6c01491755ffaef893ea73009","signature":"b75a01a468f49abde3b5c81c0713d819845d113bb5a66bd3a576fe40b10927271d9d25c1c1bbf66b73e7b3c2b6399648"}}),
         ).unwrap();
-        let beacon = get_drand_beacon(&payload).ok();
+        let beacon = get_drand_beacon(&payload, &chains).ok();
         assert!(beacon.is_none());
 
         let payload = generate_payload_hex(
             json!({"beacon":{"round":4088011,"randomness":"9f020c15bbee97470e2cebe5f600b66cccf600b6c01491755ffaef893ea73009","signature":"b75a01a468f49abde3b5c81c0713d819845d113bb5a66bd3a576fe40b10927271d9d25c1c1bbf66b73e7b3c2b63996483333"}}),
         ).unwrap();
-        let beacon = get_drand_beacon(&payload).ok();
+        let beacon = get_drand_beacon(&payload, &chains).ok();
         assert!(beacon.is_none());
     }
 
+    #[actix_web::test]
+    async fn test_get_drand_beacon_abi_encoded() {
+        generate_log();
+        check_if_dotenv_is_loaded!();
+        let signature = hex!("857325b9d4d9e81b32ff98f0da6fc2c6af02b10207ef148d2d39b82b77159d766a9edf8a68a793135890a7dfa66166a7");
+
+        let abi_encode = |round: u64, signature: &[u8]| {
+            let mut payload = beacon_selector().to_vec();
+            payload.extend([0u8; 24]);
+            payload.extend(round.to_be_bytes());
+            payload.extend([0u8; 31]);
+            payload.push(0x40);
+            payload.extend([0u8; 31]);
+            payload.push(signature.len() as u8);
+            payload.extend(signature);
+            payload.resize(payload.len() + (32 - signature.len() % 32) % 32, 0);
+            format!("0x{}", hex::encode(payload))
+        };
+
+        let chains = load_drand_chains();
+        let beacon = get_drand_beacon(&abi_encode(2797373, &signature), &chains).unwrap();
+        assert_eq!(beacon.round, 2797373);
+        assert_eq!(beacon.signature, hex::encode(signature));
+        assert_eq!(
+            beacon.randomness,
+            "a8482088c159d7a5c9a54cf599c686febbef00d97d0c460dfee3cd80ff371dd9"
+        );
+
+        assert!(get_drand_beacon(&abi_encode(2797374, &signature), &chains).is_err());
+
+        let truncated = abi_encode(2797373, &signature);
+        assert!(get_drand_beacon(&truncated[..truncated.len() - 64], &chains).is_err());
+    }
+
+    fn load_env() {
+        LOAD_ENV.call_once(|| {
+            dotenv().unwrap();
+            actix_web::rt::System::new()
+                .block_on(load_env_from_json())
+                .unwrap();
+        });
+    }
+
+    mod properties {
+        use proptest::{collection::vec, prelude::*};
+
+        use super::load_env;
+        use crate::{
+            drand::{
+                beacon_selector, get_drand_beacon, is_querying_pending_beacon, load_drand_chains,
+            },
+            rollup::input::{has_input_inside_input, RollupInput},
+        };
+
+        /**
+         * ABI words around the offsets and lengths the beacon decoder reads
+         */
+        fn abi_word() -> impl Strategy<Value = u64> {
+            prop_oneof![0..256u64, any::<u64>(), Just(u64::MAX), Just(u64::MAX - 31)]
+        }
+
+        proptest! {
+            #[test]
+            fn prop_payload_decoders_never_panic(text in ".*", bytes in vec(any::<u8>(), 0..256)) {
+                load_env();
+                let chains = load_drand_chains();
+                for payload in [text, format!("0x{}", hex::encode(bytes))] {
+                    let input = RollupInput::builder().with_payload(payload.clone()).build();
+                    let _ = get_drand_beacon(&payload, &chains);
+                    let _ = has_input_inside_input(&input);
+                    let _ = input.decoded_inspect();
+                    let _ = is_querying_pending_beacon(&input);
+                }
+            }
+
+            #[test]
+            fn prop_abi_beacon_decoder_never_panics(words in vec(abi_word(), 0..5), tail in vec(any::<u8>(), 0..128)) {
+                load_env();
+                let mut payload = beacon_selector().to_vec();
+                for word in words {
+                    payload.extend([0u8; 24]);
+                    payload.extend(word.to_be_bytes());
+                }
+                payload.extend(tail);
+                let chains = load_drand_chains();
+                let _ = get_drand_beacon(&format!("0x{}", hex::encode(payload)), &chains);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_get_drand_beacon_caches_verified_round() {
+        generate_log();
+        check_if_dotenv_is_loaded!();
+        let key = std::env::var("DRAND_PUBLIC_KEY").unwrap();
+        let signature = hex!("8f4c029827e0c1d6f5db875c1927bc79cb15188e046de5ad627cb7d1efce87b1f3de99a045b770632333a41af3abf352");
+        let payload = generate_payload_hex(
+            json!({"beacon":{"round":2832127,"randomness":"","signature":hex::encode(signature)}}),
+        )
+        .unwrap();
+
+        let chains = load_drand_chains();
+        let first = get_drand_beacon(&payload, &chains).unwrap();
+        assert!(is_verified_round(2832127, &key, &signature));
+        assert!(!is_verified_round(2832128, &key, &signature));
+
+        let cached = get_drand_beacon(&payload, &chains).unwrap();
+        assert_eq!(first.randomness, cached.randomness);
+
+        // a cached signature is not valid for another round
+        let payload = generate_payload_hex(
+            json!({"beacon":{"round":2832128,"randomness":"","signature":hex::encode(signature)}}),
+        )
+        .unwrap();
+        assert!(get_drand_beacon(&payload, &chains).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_drand_config_update_only_from_owner() {
+        check_if_dotenv_is_loaded!();
+        std::env::set_var(
+            "DRAND_CONFIG_OWNER",
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8",
+        );
+        let update = json!({"drand_config_update":{
+            "DRAND_PUBLIC_KEY":"83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a",
+            "DRAND_PERIOD":3,
+            "DRAND_GENESIS_TIME":1692803367,
+            "DRAND_SAFE_SECONDS":10
+        }});
+
+        let input_from = |sender: &str, payload: &serde_json::Value| {
+            let metadata = RollupInputDataMetadata::builder()
+                .with_address_sender(sender.to_string())
+                .build();
+            RollupInput::builder()
+                .with_payload(generate_payload_hex(payload).unwrap())
+                .with_metadata(metadata)
+                .with_request_type(RollupState::Advance)
+                .build()
+        };
+
+        let owner = input_from("0x70997970c51812dc3a010c7d01b50e0d17dc79c8", &update);
+        let drand = get_drand_config_update(&owner).unwrap();
+        assert_eq!(drand.DRAND_SAFE_SECONDS, Some(10));
+
+        let stranger = input_from("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266", &update);
+        assert!(get_drand_config_update(&stranger).is_err());
+
+        let mut invalid_key = update.clone();
+        invalid_key["drand_config_update"]["DRAND_PUBLIC_KEY"] = json!("0x123");
+        let invalid_key = input_from("0x70997970c51812dc3a010c7d01b50e0d17dc79c8", &invalid_key);
+        assert!(get_drand_config_update(&invalid_key).is_err());
+
+        let mut zero_period = update.clone();
+        zero_period["drand_config_update"]["DRAND_PERIOD"] = json!(0);
+        let zero_period = input_from("0x70997970c51812dc3a010c7d01b50e0d17dc79c8", &zero_period);
+        assert!(get_drand_config_update(&zero_period).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_update_drand_config_endpoint_disabled_by_default() {
+        check_if_dotenv_is_loaded!();
+
+        let app_state = web::Data::new(AppState::new());
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::update_drand_config);
+        let app = test::init_service(app).await;
+
+        let req = test::TestRequest::put()
+            .uri("/update_drand_config")
+            .set_json(DrandEnv {
+                DRAND_PUBLIC_KEY: "0x123".to_string(),
+                DRAND_PERIOD: None,
+                DRAND_GENESIS_TIME: None,
+                DRAND_SAFE_SECONDS: Some(1000),
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+    }
+
+    #[actix_web::test]
+    async fn test_get_drand_beacon_from_fallback_chain() {
+        generate_log();
+        check_if_dotenv_is_loaded!();
+        // League of Entropy mainnet, chained beacons need the previous signature
+        std::env::set_var(
+            "DRAND_CHAINS",
+            json!([{
+                "name": "mainnet",
+                "scheme": "pedersen-bls-chained",
+                "public_key": "868f005eb8e6e4ca0a47c8a77ceaa5309a47978a7c71bc5cce96366b5d7a569937c529eeda66c7293784a9402801af31",
+                "period": 30,
+                "genesis_time": 1595431050
+            }])
+            .to_string(),
+        );
+        let beacon = json!({
+            "round": 72785,
+            "randomness": "",
+            "signature": "82f5d3d2de4db19d40a6980e8aa37842a0e55d1df06bd68bddc8d60002e8e959eb9cfa368b3c1b77d18f02a54fe047b80f0989315f83b12a74fd8679c4f12aae86eaf6ab5690b34f1fddd50ee3cc6f6cdf59e95526d5a5d82aaa84fa6f181e42",
+            "previous_signature": "a609e19a03c2fcc559e8dae14900aaefe517cb55c840f6e69bc8e4f66c8d18e8a609685d9917efbfb0c37f058c2de88f13d297c7e19e0ab24813079efe57a182554ff054c7638153f9b26a60e7111f71a0ff63d9571704905d3ca6df0b031747",
+            "chain": "mainnet"
+        });
+
+        let payload = generate_payload_hex(json!({ "beacon": beacon })).unwrap();
+        let chains = load_drand_chains();
+        let drand_beacon = get_drand_beacon(&payload, &chains).unwrap();
+        assert_eq!(drand_beacon.chain.as_deref(), Some("mainnet"));
+
+        let mut without_previous = beacon.clone();
+        without_previous["previous_signature"] = serde_json::Value::Null;
+        without_previous["round"] = json!(72786);
+        let payload = generate_payload_hex(json!({ "beacon": without_previous })).unwrap();
+        assert!(get_drand_beacon(&payload, &chains).is_err());
+
+        let mut unknown_chain = beacon.clone();
+        unknown_chain["chain"] = json!("testnet");
+        let payload = generate_payload_hex(json!({ "beacon": unknown_chain })).unwrap();
+        assert!(get_drand_beacon(&payload, &chains).is_err());
+
+        // the primary chain key does not verify mainnet beacons
+        let mut primary = beacon.clone();
+        primary["chain"] = serde_json::Value::Null;
+        let payload = generate_payload_hex(json!({ "beacon": primary })).unwrap();
+        assert!(get_drand_beacon(&payload, &chains).is_err());
+    }
+
+    /**
+     * Feed a recording back, the mock rollup server answers /finish with the recorded responses
+     * and every DApp call must get the recorded status and body.
+     */
+    async fn replay(recording: &str) {
+        let interactions = recording
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str::<Interaction>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        let rollup_calls = |expected: &str| {
+            interactions
+                .iter()
+                .filter(|interaction| {
+                    matches!(interaction, Interaction::Rollup { path, .. } if path == expected)
+                })
+                .count()
+        };
+        let finish_responses = interactions
+            .iter()
+            .filter_map(|interaction| match interaction {
+                Interaction::Rollup {
+                    path,
+                    status,
+                    response,
+                    ..
+                } if path == "/finish" => {
+                    let responder: Box<dyn Responder> = Box::new(
+                        status_code(*status)
+                            .insert_header("Content-Type", "application/json")
+                            .body(match response {
+                                serde_json::Value::Null => String::new(),
+                                response => response.to_string(),
+                            }),
+                    );
+                    Some(responder)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let server = SERVER.get_server();
+        if !finish_responses.is_empty() {
+            server.expect(
+                Expectation::matching(request::method_path("POST", "/finish"))
+                    .times(finish_responses.len())
+                    .respond_with(cycle(finish_responses)),
+            );
+        }
+        for path in ["/report", "/notice"] {
+            server.expect(
+                Expectation::matching(request::method_path("POST", path))
+                    .times(rollup_calls(path))
+                    .respond_with(status_code(200)),
+            );
+        }
+        BIND_SERVER.call_once(|| {
+            let url = server.url_str("");
+            let url = url.trim_end_matches("/");
+            std::env::set_var("ROLLUP_HTTP_SERVER_URL", url);
+        });
+
+        let app_state = web::Data::new(AppState::new());
+        let app = App::new()
+            .wrap(generate_log())
+            .app_data(app_state.clone())
+            .service(routes::request_random)
+            .service(routes::consume_buffer)
+            .service(routes::update_drand_config)
+            .service(routes::register_randomness_request)
+            .service(routes::fulfil_randomness_request)
+            .service(routes::open_timelock);
+        let app = test::init_service(app).await;
+
+        for interaction in interactions.iter() {
+            if let Interaction::Dapp {
+                method,
+                path,
+                request,
+                status,
+                response,
+            } = interaction
+            {
+                let mut req = test::TestRequest::default()
+                    .method(Method::from_bytes(method.as_bytes()).unwrap())
+                    .uri(path);
+                if !request.is_null() {
+                    req = req.set_json(request);
+                }
+                let resp = test::call_service(&app, req.to_request()).await;
+                assert_eq!(resp.status().as_u16(), *status, "{} {}", method, path);
+                let body = test::read_body(resp).await;
+                assert_eq!(
+                    std::str::from_utf8(&body).unwrap(),
+                    response,
+                    "{} {}",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_replay_random_with_beacon() {
+        check_if_dotenv_is_loaded!();
+        replay(include_str!("../fixtures/replay_random_with_beacon.jsonl")).await;
+    }
+
+    /**
+     * Replay a recording made with RECORD_FILE, run with
+     * `REPLAY_FILE=requests.jsonl cargo test replay_recorded_file -- --ignored`
+     */
+    #[ignore]
+    #[actix_web::test]
+    async fn replay_recorded_file() {
+        check_if_dotenv_is_loaded!();
+        let path = std::env::var("REPLAY_FILE").expect("REPLAY_FILE is not set");
+        replay(&std::fs::read_to_string(path).unwrap()).await;
+    }
+
+    #[actix_web::test]
+    async fn test_record_dapp_calls() {
+        check_if_dotenv_is_loaded!();
+        let path = std::env::temp_dir().join(format!("record-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        std::env::set_var("RECORD_FILE", &path);
+
+        let input = mock_factory(None, 0).unwrap();
+        let mut input: serde_json::Value = input;
+        input["data"]["metadata"]["timestamp"] = json!(1701299740);
+        let beacon = DrandBeacon::builder()
+            .with_round(2832127)
+            .with_signature("8f4c029827e0c1d6f5db875c1927bc79cb15188e046de5ad627cb7d1efce87b1f3de99a045b770632333a41af3abf352".to_string())
+            .build();
+        let mut beacon_input = mock_factory(Some(json!({ "beacon": beacon })), 1).unwrap();
+        beacon_input["data"]["metadata"]["timestamp"] = json!(1701299745);
+
+        let server = SERVER.get_server();
+        server.expect(
+            Expectation::matching(request::method_path("POST", "/finish"))
+                .times(3)
+                .respond_with(cycle![
+                    json_encoded(input),
+                    json_encoded(beacon_input),
+                    status_code(202)
+                ]),
+        );
+        BIND_SERVER.call_once(|| {
+            let url = server.url_str("");
+            let url = url.trim_end_matches("/");
+            std::env::set_var("ROLLUP_HTTP_SERVER_URL", url);
+        });
+
+        let app_state = web::Data::new(AppState::new());
+        let app = App::new()
+            .wrap(from_fn(record_dapp_calls))
+            .app_data(app_state.clone())
+            .service(routes::request_random)
+            .service(routes::consume_buffer);
+        let app = test::init_service(app).await;
+
+        call_finish!(&app);
+        let req = test::TestRequest::with_uri("/random?timestamp=1701299740").to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        test::call_service(&app, req).await;
+        let req = test::TestRequest::with_uri("/finish")
+            .method(Method::POST)
+            .set_json(json!({"status": "accept"}))
+            .to_request();
+        test::call_service(&app, req).await;
+        std::env::remove_var("RECORD_FILE");
+
+        // the rollup exchanges are recorded too, this is the same session as the replay fixture
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        let dapp_calls = |recording: &str| {
+            recording
+                .lines()
+                .map(|line| serde_json::from_str::<Interaction>(line).unwrap())
+                .filter(|interaction| matches!(interaction, Interaction::Dapp { .. }))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            dapp_calls(&recorded),
+            dapp_calls(include_str!("../fixtures/replay_random_with_beacon.jsonl"))
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
     // #[actix_web::test]
     // async fn test_update_key() {
     //     env_logger::builder().is_test(true).try_init().unwrap();
@@ -415,10 +1321,177 @@ mod middleware_tests {
     //         .set_json(drand_env)
     //         .to_http_request();
     // }
+    fn schema_keys(spec: &Value, schema: &str, field: &str) -> BTreeSet<String> {
+        let value = &spec["components"]["schemas"][schema][field];
+        match value {
+            Value::Object(map) => map.keys().cloned().collect(),
+            Value::Array(list) => list
+                .iter()
+                .map(|v| v.as_str().unwrap().to_string())
+                .collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    fn object_keys(value: &Value) -> BTreeSet<String> {
+        value.as_object().unwrap().keys().cloned().collect()
+    }
+
+    #[actix_web::test]
+    async fn test_openapi_documents_every_route() {
+        let spec = openapi_document();
+
+        let documented: BTreeSet<(String, String)> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, methods)| {
+                let path = path.clone();
+                methods
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect();
+        let routes = [
+            ("post", "/finish"),
+            ("get", "/random"),
+            ("post", "/consumers/{name}/finish"),
+            ("get", "/consumers/{name}/random"),
+            ("put", "/update_drand_config"),
+            ("post", "/randomness_request"),
+            ("get", "/randomness_request/{id}"),
+            ("post", "/consumers/{name}/randomness_request"),
+            ("get", "/consumers/{name}/randomness_request/{id}"),
+            ("get", "/timelock/{id}"),
+            ("get", "/openapi.json"),
+        ]
+        .map(|(method, path)| (method.to_string(), path.to_string()));
+        assert_eq!(documented, BTreeSet::from(routes));
+
+        let random = &spec["paths"]["/random"]["get"];
+        let parameters = random["parameters"].as_array().unwrap();
+        assert_eq!(parameters[0]["name"], "timestamp");
+        assert_eq!(parameters[0]["in"], "query");
+        assert_eq!(parameters[0]["required"], true);
+        assert_eq!(parameters[1]["name"], "outputs");
+        assert_eq!(parameters[1]["required"], false);
+
+        let headers = object_keys(&random["responses"]["200"]["headers"]);
+        assert_eq!(
+            headers,
+            BTreeSet::from([
+                routes::DRAND_CHAINS_HEADER.to_string(),
+                routes::RANDOMNESS_DERIVATION_HEADER.to_string()
+            ])
+        );
+        assert_eq!(
+            random["responses"]["400"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Error"
+        );
+
+        let consumer = &spec["paths"]["/consumers/{name}/random"]["get"];
+        assert_eq!(consumer["parameters"][0]["name"], "name");
+        assert_eq!(consumer["parameters"][0]["in"], "path");
+        assert!(consumer["responses"]["404"].is_object());
+    }
+
+    #[actix_web::test]
+    async fn test_openapi_schemas_match_models() {
+        let spec = openapi_document();
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(1)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        let input = serde_json::to_value(input).unwrap();
+        assert_eq!(
+            object_keys(&input),
+            schema_keys(&spec, "RollupInput", "properties")
+        );
+        assert_eq!(
+            object_keys(&input["data"]),
+            schema_keys(&spec, "RollupInputData", "properties")
+        );
+        assert_eq!(
+            object_keys(&input["data"]["metadata"]),
+            schema_keys(&spec, "RollupInputDataMetadata", "properties")
+        );
+
+        let drand = serde_json::to_value(DrandEnv {
+            DRAND_PUBLIC_KEY: "".into(),
+            DRAND_PERIOD: None,
+            DRAND_GENESIS_TIME: None,
+            DRAND_SAFE_SECONDS: None,
+        })
+        .unwrap();
+        assert_eq!(
+            object_keys(&drand),
+            schema_keys(&spec, "DrandEnv", "properties")
+        );
+
+        let request = serde_json::to_value(RandomnessRequest {
+            id: "".into(),
+            input_index: 0,
+            round: 0,
+            randomness: None,
+            fulfilled_round: None,
+        })
+        .unwrap();
+        assert_eq!(
+            object_keys(&request),
+            schema_keys(&spec, "RandomnessRequest", "properties")
+        );
+
+        let timelock = serde_json::to_value(TimelockCiphertext {
+            id: "".into(),
+            round: 0,
+            ciphertext: vec![],
+            plaintext: None,
+        })
+        .unwrap();
+        assert_eq!(
+            object_keys(&timelock),
+            schema_keys(&spec, "TimelockCiphertext", "properties")
+        );
+
+        let status = json!({"status": "accept"});
+        assert_eq!(
+            object_keys(&status),
+            schema_keys(&spec, "RequestRollups", "required")
+        );
+        assert!(serde_json::from_value::<RequestRollups>(status).is_ok());
+        assert!(serde_json::from_value::<RequestRollups>(json!({})).is_err());
+
+        // Timestamp is documented as query parameters of /random
+        assert!(serde_json::from_value::<Timestamp>(json!({"timestamp": 1})).is_ok());
+        assert!(serde_json::from_value::<Timestamp>(json!({})).is_err());
+
+        let id = json!({"id": "1"});
+        assert_eq!(
+            object_keys(&id),
+            schema_keys(&spec, "RandomnessRequestId", "required")
+        );
+        assert!(serde_json::from_value::<RandomnessRequestId>(id).is_ok());
+        assert!(serde_json::from_value::<RandomnessRequestId>(json!({})).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_serve_openapi() {
+        let app = test::init_service(App::new().service(routes::openapi)).await;
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body, openapi_document());
+    }
+
     #[actix_web::test]
     async fn test_verify_fast() {
         const PK_HEX3: [u8; 96] = hex!("a0b862a7527fee3a731bcb59280ab6abd62d5c0b6ea03dc4ddf6612fdfc9d01f01c31542541771903475eb1ec6615f8d0df0b8b6dce385811d6dcf8cbefb8759e5e616a3dfd054c928940766d9a5b9db91e3b697e5d70a975181e007f87fca5e");
-        let pk = G2Pubkey::from_fixed(PK_HEX3).unwrap();
+        let pk = G2PubkeyFastnet::from_fixed(PK_HEX3).unwrap();
 
         // https://api3.drand.sh/dbd506d6ef76e5f386f41c651dcb808c5bcbd75471cc4eafa3f4df7ad4e4c493/public/1
         let signature = hex::decode("9544ddce2fdbe8688d6f5b4f98eed5d63eee3902e7e162050ac0f45905a55657714880adabe3c3096b92767d886567d0").unwrap();
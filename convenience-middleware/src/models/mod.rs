@@ -1,17 +1,34 @@
 pub mod structs {
-    use std::{borrow::BorrowMut, cell::Cell, collections::VecDeque, error::Error, sync::Arc};
+    use std::{
+        borrow::BorrowMut,
+        cell::Cell,
+        collections::{HashMap, VecDeque},
+        error::Error,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, RwLock,
+        },
+    };
 
     use dotenvy::var;
-    use log::info;
     use serde::{Deserialize, Serialize};
     #[cfg(test)]
     use serde_json::json;
-    use sha3::{Digest, Sha3_256};
     use tokio::sync::Mutex;
+    use tracing::{info, warn};
+    use utoipa::{IntoParams, ToSchema};
 
-    use crate::rollup::input::RollupInput;
+    use crate::{
+        consumers::{load_consumers, route_input, DEFAULT_CONSUMER},
+        derivation::{consumer_namespace, request_namespace, RandomnessDerivation},
+        drand::load_drand_chains,
+        errors::CheckerError,
+        rollup::input::{has_input_inside_input, RollupInput, RollupInputDataMetadata},
+        tlock::decrypt_timelock,
+    };
 
-    #[derive(serde::Deserialize, serde::Serialize)]
+    #[derive(serde::Deserialize, serde::Serialize, Clone, ToSchema)]
     #[allow(non_snake_case)]
     pub struct DrandEnv {
         pub DRAND_PUBLIC_KEY: String,
@@ -20,13 +37,19 @@ pub mod structs {
         pub DRAND_SAFE_SECONDS: Option<u64>,
     }
 
-    #[derive(Serialize)]
+    #[derive(Deserialize)]
+    pub struct PayloadWithDrandConfig {
+        pub drand_config_update: DrandEnv,
+    }
+
+    #[derive(Serialize, Deserialize)]
     pub struct Item {
         pub request: String,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Serialize, Deserialize, ToSchema)]
     pub struct RequestRollups {
+        #[schema(example = "accept")]
         status: String,
     }
 
@@ -34,16 +57,72 @@ pub mod structs {
         is_holding: bool,
     }
 
-    #[derive(Deserialize)]
+    #[derive(Deserialize, IntoParams)]
+    #[into_params(parameter_in = Query)]
     pub struct Timestamp {
         pub timestamp: u64,
+        /**
+         * Number of 32 bytes outputs, only the HKDF derivation expands to more than one
+         */
+        #[serde(default)]
+        #[param(minimum = 1, maximum = 255, default = 1)]
+        pub outputs: Option<usize>,
     }
 
-    #[derive(Default)]
+    impl Timestamp {
+        pub fn outputs(&self) -> usize {
+            self.outputs.unwrap_or(1)
+        }
+    }
+
+    #[derive(Deserialize, ToSchema)]
+    pub struct RandomnessRequestId {
+        pub id: String,
+    }
+
+    /**
+     * A randomness request registered by the DApp while processing an input.
+     * It is bound to a future round and fulfilled by the first beacon of that round or a later one.
+     */
+    #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+    pub struct RandomnessRequest {
+        pub id: String,
+        pub input_index: u128,
+        pub round: u64,
+        pub randomness: Option<String>,
+        /**
+         * The round of the beacon that fulfilled the request, relayers may skip past the target round
+         */
+        #[serde(default)]
+        pub fulfilled_round: Option<u64>,
+    }
+
+    /**
+     * Position of an advance input on L1, used to order beacons against requests.
+     */
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    pub struct InputRef {
+        pub index: u128,
+        pub epoch_index: u128,
+        pub timestamp: u64,
+    }
+
+    impl From<&RollupInputDataMetadata> for InputRef {
+        fn from(metadata: &RollupInputDataMetadata) -> Self {
+            InputRef {
+                index: metadata.input_index,
+                epoch_index: metadata.epoch_index,
+                timestamp: metadata.timestamp,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Default, Clone)]
     pub struct Beacon {
         pub timestamp: u64,
         pub round: u64,
         pub randomness: String,
+        pub delivered_in: Option<InputRef>,
     }
 
     #[derive(Default)]
@@ -53,6 +132,24 @@ pub mod structs {
         pub fn builder() -> BeaconBuilder {
             BeaconBuilder::default()
         }
+
+        /**
+         * A beacon can only answer a request if it was delivered by a later input,
+         * otherwise it was already public when the request was made.
+         */
+        pub fn is_delivered_after(&self, request: Option<InputRef>, query_timestamp: u64) -> bool {
+            let delivered_in = match self.delivered_in {
+                Some(delivered_in) => delivered_in,
+                None => return true,
+            };
+            if delivered_in.timestamp < query_timestamp {
+                return false;
+            }
+            match request {
+                Some(request) => request.index < delivered_in.index,
+                None => true,
+            }
+        }
     }
 
     impl BeaconBuilder {
@@ -79,6 +176,11 @@ pub mod structs {
             self
         }
 
+        pub fn with_delivered_in(mut self, delivered_in: Option<InputRef>) -> BeaconBuilder {
+            self.0.delivered_in = delivered_in;
+            self
+        }
+
         pub fn build(self) -> Beacon {
             self.0
         }
@@ -89,11 +191,191 @@ pub mod structs {
         pub beacon: DrandBeacon,
     }
 
+    #[derive(Deserialize, Debug)]
+    pub struct TimelockInput {
+        pub id: String,
+        pub ciphertext: String,
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct PayloadWithTimelock {
+        pub timelock: TimelockInput,
+    }
+
+    /**
+     * A tlock ciphertext sealed until its round, the plaintext is hex encoded once opened.
+     */
+    #[derive(Serialize, Clone, Debug, ToSchema)]
+    pub struct TimelockCiphertext {
+        pub id: String,
+        pub round: u64,
+        #[serde(skip)]
+        pub ciphertext: Vec<u8>,
+        pub plaintext: Option<String>,
+    }
+
     #[derive(Default, Serialize, Deserialize, Debug, Clone)]
     pub struct DrandBeacon {
         pub round: u64,
         pub signature: String,
         pub randomness: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub previous_signature: Option<String>,
+        /**
+         * Name of the configured chain, the primary chain when missing
+         */
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub chain: Option<String>,
+    }
+
+    /**
+     * The drand schemes, as reported by the chain info endpoint
+     */
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    pub enum DrandScheme {
+        #[default]
+        #[serde(rename = "bls-unchained-g1-rfc9380")]
+        UnchainedOnG1Rfc9380,
+        #[serde(rename = "bls-unchained-on-g1")]
+        UnchainedOnG1,
+        #[serde(rename = "pedersen-bls-chained")]
+        PedersenChained,
+        #[serde(rename = "pedersen-bls-unchained")]
+        PedersenUnchained,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct DrandChain {
+        pub name: String,
+        #[serde(default)]
+        pub scheme: DrandScheme,
+        pub public_key: String,
+        pub period: u64,
+        pub genesis_time: u64,
+    }
+
+    impl DrandChain {
+        pub fn beacon_time(&self, round: u64) -> u64 {
+            round
+                .saturating_mul(self.period)
+                .saturating_add(self.genesis_time)
+        }
+
+        /**
+         * The first round whose beacon time is after the timestamp
+         */
+        pub fn round_after(&self, timestamp: u64) -> u64 {
+            (timestamp.saturating_sub(self.genesis_time) / self.period.max(1)) + 1
+        }
+    }
+
+    /**
+     * How beacons from several chains answer /random, chosen by the DRAND_CHAIN_POLICY env.
+     * first: the first chain whose beacon passed the safe time, combine: hash the beacons of every chain.
+     */
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum ChainPolicy {
+        #[default]
+        First,
+        Combine,
+    }
+
+    impl FromStr for ChainPolicy {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.to_lowercase().as_str() {
+                "first" => Ok(ChainPolicy::First),
+                "combine" => Ok(ChainPolicy::Combine),
+                _ => Err(format!("Unknown chain policy {}", value)),
+            }
+        }
+    }
+
+    impl ChainPolicy {
+        pub fn from_env() -> ChainPolicy {
+            match var("DRAND_CHAIN_POLICY") {
+                Ok(value) => value.parse().unwrap_or_else(|e| {
+                    warn!("{}, using first chain policy", e);
+                    ChainPolicy::First
+                }),
+                Err(_) => ChainPolicy::default(),
+            }
+        }
+    }
+
+    /**
+     * How inputs are routed to the consumers, chosen by the CONSUMER_ROUTE env.
+     * field:<name>: the consumer named by that field of the JSON payload (default field:consumer),
+     * prefix: the consumer whose hex prefix starts the payload.
+     */
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum ConsumerRoute {
+        Field(String),
+        Prefix,
+    }
+
+    impl Default for ConsumerRoute {
+        fn default() -> Self {
+            ConsumerRoute::Field("consumer".to_string())
+        }
+    }
+
+    impl FromStr for ConsumerRoute {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.split_once(':') {
+                None if value == "prefix" => Ok(ConsumerRoute::Prefix),
+                Some(("field", field)) if !field.is_empty() => {
+                    Ok(ConsumerRoute::Field(field.to_string()))
+                }
+                _ => Err(format!("Unknown consumer route {}", value)),
+            }
+        }
+    }
+
+    impl ConsumerRoute {
+        pub fn from_env() -> ConsumerRoute {
+            match var("CONSUMER_ROUTE") {
+                Ok(value) => value.parse().unwrap_or_else(|e| {
+                    warn!("{}, using field:consumer route", e);
+                    ConsumerRoute::default()
+                }),
+                Err(_) => ConsumerRoute::default(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct ConsumerConfig {
+        pub name: String,
+        #[serde(default)]
+        pub prefix: Option<String>,
+    }
+
+    /**
+     * A DApp sharing the middleware. Beacons reach every consumer,
+     * inputs, the randomness counter and randomness requests belong to one.
+     */
+    #[derive(Clone)]
+    pub struct Consumer {
+        pub name: String,
+        /**
+         * Lowercase hex without 0x
+         */
+        pub prefix: Option<String>,
+        pub input_buffer_manager: Arc<Mutex<InputBufferManager>>,
+    }
+
+    /**
+     * The seed returned by /random and the chains whose beacons produced it
+     */
+    #[derive(Debug, PartialEq)]
+    pub struct Randomness {
+        pub value: String,
+        pub chains: Vec<String>,
+        pub derivation: RandomnessDerivation,
     }
 
     #[cfg(test)]
@@ -138,35 +420,123 @@ pub mod structs {
         }
     }
 
+    /**
+     * Timelocks keep their ciphertext in a snapshot, unlike the /timelock response
+     */
+    #[derive(Serialize, Deserialize)]
+    pub struct TimelockSnapshot {
+        pub id: String,
+        pub round: u64,
+        #[serde(with = "hex")]
+        pub ciphertext: Vec<u8>,
+        pub plaintext: Option<String>,
+    }
+
+    /**
+     * What an InputBufferManager needs to resume after a restart
+     */
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct InputBufferSnapshot {
+        pub messages: VecDeque<Item>,
+        pub last_beacon: Option<Beacon>,
+        pub fallback_beacons: HashMap<String, Beacon>,
+        pub pending_beacon_timestamp: u64,
+        pub randomness_salt: u64,
+        pub current_input: Option<InputRef>,
+        pub randomness_requests: HashMap<String, RandomnessRequest>,
+        pub timelocks: Vec<TimelockSnapshot>,
+    }
+
+    /**
+     * Written on shutdown and restored on start in host mode, keyed by consumer name
+     */
+    #[derive(Serialize, Deserialize, Default)]
+    pub struct Snapshot {
+        pub consumers: HashMap<String, InputBufferSnapshot>,
+    }
+
     pub struct InputBufferManager {
         pub messages: VecDeque<Item>,
         pub flag_to_hold: Flag,
         pub request_count: Cell<usize>,
         pub last_beacon: Cell<Option<Beacon>>,
+        pub fallback_beacons: HashMap<String, Beacon>,
         pub pending_beacon_timestamp: Cell<u64>,
         pub randomness_salt: Cell<u64>,
+        pub current_input: Cell<Option<InputRef>>,
+        pub randomness_requests: HashMap<String, RandomnessRequest>,
+        pub timelocks: HashMap<String, TimelockCiphertext>,
         pub is_inspecting: bool,
     }
 
+    /**
+     * How the middleware is running, chosen by the RUN_MODE env (production, host or test).
+     * Inspects only block /random inside the Cartesi Machine and in tests, in host mode the
+     * rollup server can interleave inspects with advances.
+     */
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum RunMode {
+        Production,
+        #[default]
+        Host,
+        Test,
+    }
+
+    impl FromStr for RunMode {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.to_lowercase().as_str() {
+                "production" => Ok(RunMode::Production),
+                "host" => Ok(RunMode::Host),
+                "test" => Ok(RunMode::Test),
+                _ => Err(format!("Unknown run mode {}", value)),
+            }
+        }
+    }
+
+    impl RunMode {
+        pub fn from_env() -> RunMode {
+            match var("RUN_MODE") {
+                Ok(value) => value.parse().unwrap_or_else(|e| {
+                    warn!("{}, using host mode", e);
+                    RunMode::Host
+                }),
+                Err(_) => RunMode::default(),
+            }
+        }
+    }
+
     pub struct AppState {
         pub input_buffer_manager: Arc<Mutex<InputBufferManager>>,
-        pub drand_period: u64,
-        pub drand_genesis_time: u64,
-        pub safe_seconds: u64,
+        /**
+         * Shared by every consumer, a drand config update changes it
+         */
+        pub safe_seconds: Arc<AtomicU64>,
         pub version: String,
+        pub mode: RunMode,
+        /**
+         * The primary chain first, then the fallback chains.
+         * Loaded once from the env, a drand config update changes the primary chain.
+         * Beacon times and request rounds come from these chains.
+         */
+        pub chains: Arc<RwLock<Vec<DrandChain>>>,
+        pub chain_policy: ChainPolicy,
+        pub derivation: RandomnessDerivation,
+        /**
+         * The consumer whose queue is input_buffer_manager
+         */
+        pub consumer: String,
+        /**
+         * Every consumer, the default one first
+         */
+        pub consumers: Vec<Consumer>,
+        pub consumer_route: ConsumerRoute,
     }
 
     impl AppState {
         pub fn new() -> AppState {
-            let manager = InputBufferManager::default();
-            let drand_period = var("DRAND_PERIOD")
-                .expect("Missing env DRAND_PERIOD")
-                .parse::<u64>()
-                .unwrap();
-            let drand_genesis_time = var("DRAND_GENESIS_TIME")
-                .expect("Missing env DRAND_GENESIS_TIME")
-                .parse::<u64>()
-                .unwrap();
+            let manager = Arc::new(Mutex::new(InputBufferManager::default()));
             let safe_seconds = var("DRAND_SAFE_SECONDS")
                 .expect("Missing env DRAND_SAFE_SECONDS")
                 .parse::<u64>()
@@ -174,62 +544,256 @@ pub mod structs {
             let version: Option<&str> = option_env!("CARGO_PKG_VERSION");
             let version = version.unwrap_or("unknown").to_string();
             AppState {
-                input_buffer_manager: Arc::new(Mutex::new(manager)),
-                drand_period,
-                drand_genesis_time,
-                safe_seconds,
+                input_buffer_manager: manager.clone(),
+                safe_seconds: Arc::new(AtomicU64::new(safe_seconds)),
                 version,
+                mode: RunMode::from_env(),
+                chains: Arc::new(RwLock::new(load_drand_chains())),
+                chain_policy: ChainPolicy::from_env(),
+                derivation: RandomnessDerivation::from_env(),
+                consumer: DEFAULT_CONSUMER.to_string(),
+                consumers: load_consumers(manager),
+                consumer_route: ConsumerRoute::from_env(),
+            }
+        }
+        /**
+         * The primary chain first, then the fallback chains
+         */
+        pub fn drand_chains(&self) -> Vec<DrandChain> {
+            self.chains
+                .read()
+                .map(|chains| chains.clone())
+                .unwrap_or_default()
+        }
+        pub fn primary_chain(&self) -> Option<DrandChain> {
+            self.drand_chains().into_iter().next()
+        }
+        pub fn safe_seconds(&self) -> u64 {
+            self.safe_seconds.load(Ordering::Relaxed)
+        }
+        /**
+         * Apply a drand config update to the primary chain and the safe seconds of every consumer,
+         * the fallback chains stay as they are
+         */
+        pub fn update_drand_config(&self, drand: &DrandEnv) {
+            if let Ok(mut chains) = self.chains.write() {
+                if let Some(primary) = chains.first_mut() {
+                    primary.public_key = drand.DRAND_PUBLIC_KEY.clone();
+                    if let Some(period) = drand.DRAND_PERIOD {
+                        primary.period = period;
+                    }
+                    if let Some(genesis_time) = drand.DRAND_GENESIS_TIME {
+                        primary.genesis_time = genesis_time;
+                    }
+                }
             }
+            if let Some(safe_seconds) = drand.DRAND_SAFE_SECONDS {
+                self.safe_seconds.store(safe_seconds, Ordering::Relaxed);
+            }
+        }
+        /**
+         * The same state seen from the queue of another consumer
+         */
+        pub fn for_consumer(&self, name: &str) -> Option<AppState> {
+            let consumer = self
+                .consumers
+                .iter()
+                .find(|consumer| consumer.name == name)?;
+            Some(AppState {
+                input_buffer_manager: consumer.input_buffer_manager.clone(),
+                safe_seconds: self.safe_seconds.clone(),
+                version: self.version.clone(),
+                mode: self.mode,
+                chains: self.chains.clone(),
+                chain_policy: self.chain_policy,
+                derivation: self.derivation,
+                consumer: consumer.name.clone(),
+                consumers: self.consumers.clone(),
+                consumer_route: self.consumer_route.clone(),
+            })
+        }
+        /**
+         * The state of the consumer an input is routed to.
+         * Inputs without a DApp input inside, like beacons, stay with this consumer.
+         */
+        pub fn for_input(&self, rollup_input: &RollupInput) -> AppState {
+            let name = match has_input_inside_input(rollup_input) {
+                true => route_input(&self.consumers, &self.consumer_route, rollup_input),
+                false => &self.consumer,
+            };
+            self.for_consumer(name)
+                .expect("Inputs are routed to registered consumers")
         }
-        pub fn get_randomness_for_timestamp(&self, query_timestamp: u64) -> Option<String> {
+        fn default_buffer_manager(&self) -> &Arc<Mutex<InputBufferManager>> {
+            &self.consumers[0].input_buffer_manager
+        }
+        #[cfg(test)]
+        pub fn get_randomness_for_timestamp(&self, query_timestamp: u64) -> Option<Randomness> {
+            self.get_randomness_outputs_for_timestamp(query_timestamp, 1)
+        }
+        /**
+         * The outputs must be supported by the derivation, check it before asking
+         */
+        pub fn get_randomness_outputs_for_timestamp(
+            &self,
+            query_timestamp: u64,
+            outputs: usize,
+        ) -> Option<Randomness> {
             let mut manager = match self.input_buffer_manager.try_lock() {
                 Ok(manager) => manager,
                 Err(_) => return None,
             };
-            let safe_query_timestamp = query_timestamp + self.safe_seconds;
-            match manager.last_beacon.take() {
-                Some(beacon) => {
-                    info!(
-                        "beacon time {} vs {} request time",
-                        beacon.timestamp, query_timestamp
-                    );
-                    // Check the beacon timestamp against the safe query timestamp
-                    if safe_query_timestamp < beacon.timestamp {
-                        let salt = manager.randomness_salt.take() + 1;
-                        manager.randomness_salt.set(salt);
-
-                        let mut hasher = Sha3_256::new();
-                        hasher.update([beacon.randomness.as_bytes(), &salt.to_le_bytes()].concat());
-                        let randomness = hasher.finalize();
-                        manager.flag_to_hold.release();
-                        manager.last_beacon.set(Some(beacon));
-                        Some(hex::encode(randomness))
-                    } else {
-                        manager.set_pending_beacon_timestamp(safe_query_timestamp);
-                        manager.last_beacon.set(Some(beacon));
-                        None
+            let safe_query_timestamp = query_timestamp.saturating_add(self.safe_seconds());
+            let request = manager.current_input.get();
+            let primary = manager.last_beacon.take();
+
+            // Check the beacon timestamp against the safe query timestamp
+            // and make sure it arrived after the input that is asking for it
+            let is_usable = |beacon: &Beacon| {
+                info!(
+                    "beacon time {} vs {} request time",
+                    beacon.timestamp, query_timestamp
+                );
+                safe_query_timestamp < beacon.timestamp
+                    && beacon.is_delivered_after(request, query_timestamp)
+            };
+
+            let chains = self.drand_chains();
+            let beacons = chains
+                .iter()
+                .enumerate()
+                .map(|(i, chain)| {
+                    let beacon = match i {
+                        0 => primary.as_ref(),
+                        _ => manager.fallback_beacons.get(&chain.name),
+                    };
+                    (
+                        chain.name.as_str(),
+                        beacon.filter(|beacon| is_usable(beacon)),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let seed = match self.chain_policy {
+                ChainPolicy::First => beacons
+                    .iter()
+                    .filter_map(|(name, beacon)| beacon.map(|beacon| (*name, beacon)))
+                    .min_by_key(|(_, beacon)| beacon.timestamp)
+                    .map(|(name, beacon)| (vec![beacon], vec![name.to_string()])),
+                ChainPolicy::Combine if beacons.iter().all(|(_, beacon)| beacon.is_some()) => {
+                    let seed = beacons.iter().filter_map(|(_, beacon)| *beacon).collect();
+                    let names = beacons.iter().map(|(name, _)| name.to_string()).collect();
+                    Some((seed, names))
+                }
+                ChainPolicy::Combine => None,
+            };
+
+            let randomness = match seed {
+                Some((seed, chains)) => {
+                    info!("Randomness from chains {:?}", chains);
+                    let salt = manager.randomness_salt.get() + 1;
+                    let namespace = match self.consumer.as_str() {
+                        DEFAULT_CONSUMER => None,
+                        name => Some(consumer_namespace(name)),
+                    };
+                    let seed = seed
+                        .iter()
+                        .map(|beacon| beacon.randomness.as_str())
+                        .chain(namespace.as_deref())
+                        .collect::<Vec<_>>();
+
+                    match self.derivation.derive(&seed, salt, outputs) {
+                        Some(value) => {
+                            manager.randomness_salt.set(salt);
+                            manager.flag_to_hold.release();
+                            Some(Randomness {
+                                value: hex::encode(value),
+                                chains,
+                                derivation: self.derivation,
+                            })
+                        }
+                        None => {
+                            warn!(
+                                "Cannot derive {} outputs with {}",
+                                outputs,
+                                self.derivation.as_str()
+                            );
+                            None
+                        }
                     }
                 }
                 None => {
                     manager.set_pending_beacon_timestamp(safe_query_timestamp);
                     None
                 }
+            };
+
+            manager.last_beacon.set(primary);
+            randomness
+        }
+        /**
+         * Beacons are shared, every consumer keeps the newest one and fulfils its own randomness requests
+         */
+        pub async fn keep_newest_beacon(
+            &self,
+            drand_beacon: DrandBeacon,
+            metadata: Option<&RollupInputDataMetadata>,
+        ) {
+            for consumer in &self.consumers {
+                let mut manager = consumer.input_buffer_manager.lock().await;
+                self.keep_newest_beacon_in(&mut manager, &consumer.name, &drand_beacon, metadata);
             }
         }
-        pub fn keep_newest_beacon(&self, drand_beacon: DrandBeacon) {
-            let beacon_time = (drand_beacon.round * self.drand_period) + self.drand_genesis_time;
+        fn keep_newest_beacon_in(
+            &self,
+            manager: &mut InputBufferManager,
+            consumer: &str,
+            drand_beacon: &DrandBeacon,
+            metadata: Option<&RollupInputDataMetadata>,
+        ) {
+            // every chain, the primary one included, times its rounds with its current config
+            let chains = self.drand_chains();
+            let fallback = drand_beacon
+                .chain
+                .as_ref()
+                .and_then(|name| chains.iter().skip(1).find(|chain| &chain.name == name));
+            let chain = match fallback.or(chains.first()) {
+                Some(chain) => chain,
+                None => return,
+            };
+            let beacon_time = chain.beacon_time(drand_beacon.round);
             info!(
-                "Calculated beacon time {} for round {}",
-                beacon_time, drand_beacon.round
+                "Calculated beacon time {} for round {} of chain {}",
+                beacon_time, drand_beacon.round, chain.name
             );
-            let manager = self.input_buffer_manager.try_lock().unwrap();
+
+            if fallback.is_some() {
+                let is_newer = manager
+                    .fallback_beacons
+                    .get(&chain.name)
+                    .is_none_or(|current| current.round < drand_beacon.round);
+                if is_newer {
+                    let beacon = Beacon::builder()
+                        .with_drand_beacon(drand_beacon)
+                        .with_timestamp(beacon_time)
+                        .with_delivered_in(metadata.map(InputRef::from))
+                        .build();
+                    manager.fallback_beacons.insert(chain.name.clone(), beacon);
+                }
+                return;
+            }
+
+            manager.fulfil_randomness_requests(drand_beacon, metadata, consumer, self.derivation);
+            // only the default consumer stores timelocks
+            manager.open_timelocks(drand_beacon);
             if let Some(current_beacon) = manager.last_beacon.take() {
                 if current_beacon.round < drand_beacon.round {
                     info!("Set new beacon");
 
                     let beacon = Beacon::builder()
-                        .with_drand_beacon(&drand_beacon)
+                        .with_drand_beacon(drand_beacon)
                         .with_timestamp(beacon_time)
+                        .with_delivered_in(metadata.map(InputRef::from))
                         .build();
 
                     manager.last_beacon.set(Some(beacon));
@@ -241,19 +805,83 @@ pub mod structs {
                 info!("No beacon, initializing");
 
                 let beacon = Beacon::builder()
-                    .with_drand_beacon(&drand_beacon)
+                    .with_drand_beacon(drand_beacon)
                     .with_timestamp(beacon_time)
+                    .with_delivered_in(metadata.map(InputRef::from))
                     .build();
 
                 manager.last_beacon.set(Some(beacon));
             }
         }
+        /**
+         * Register a randomness request for the input being processed by the DApp.
+         * The target round is the first one after the safe window of that input.
+         */
+        pub async fn register_randomness_request(
+            &self,
+            id: &str,
+        ) -> Result<RandomnessRequest, CheckerError> {
+            let mut manager = self.input_buffer_manager.lock().await;
+            let input = manager
+                .current_input
+                .get()
+                .ok_or(CheckerError::NoInputForRandomnessRequest)?;
+
+            if manager.randomness_requests.contains_key(id) {
+                return Err(CheckerError::DuplicatedRandomnessRequest);
+            }
+
+            let safe_timestamp = input.timestamp.saturating_add(self.safe_seconds());
+            let round = self
+                .primary_chain()
+                .ok_or(CheckerError::RandomnessError)?
+                .round_after(safe_timestamp);
+            info!(
+                "Randomness request {} registered at input {} for round {}",
+                id, input.index, round
+            );
+
+            let request = RandomnessRequest {
+                id: id.to_string(),
+                input_index: input.index,
+                round,
+                randomness: None,
+                fulfilled_round: None,
+            };
+            manager
+                .randomness_requests
+                .insert(id.to_string(), request.clone());
+            manager.set_pending_beacon_timestamp(safe_timestamp);
+
+            Ok(request)
+        }
+        pub async fn get_randomness_request(&self, id: &str) -> Option<RandomnessRequest> {
+            let manager = self.input_buffer_manager.lock().await;
+            manager.randomness_requests.get(id).cloned()
+        }
+        pub async fn store_timelock(&self, timelock: TimelockCiphertext) {
+            let mut manager = self.default_buffer_manager().lock().await;
+            if manager.timelocks.contains_key(&timelock.id) {
+                info!("Timelock {} already stored, ignoring", timelock.id);
+                return;
+            }
+            info!(
+                "Timelock {} reported as pending until round {}",
+                timelock.id, timelock.round
+            );
+            manager.timelocks.insert(timelock.id.clone(), timelock);
+        }
+        pub async fn get_timelock(&self, id: &str) -> Option<TimelockCiphertext> {
+            let manager = self.default_buffer_manager().lock().await;
+            manager.timelocks.get(id).cloned()
+        }
         pub async fn store_input(&self, rollup_input: &RollupInput) -> Result<(), Box<dyn Error>> {
             let mut manager = self.input_buffer_manager.lock().await;
             let item = rollup_input.get_item();
             match item {
                 Ok(item) => {
                     manager.messages.push_back(item);
+                    manager.request_count.set(manager.request_count.get() + 1);
                     Ok(())
                 }
                 Err(err) => Err(err),
@@ -263,20 +891,62 @@ pub mod structs {
             let mut manager = self.input_buffer_manager.lock().await;
             manager.consume_input()
         }
+        /**
+         * Remember the advance input handed to the DApp, randomness requests belong to it.
+         */
+        pub async fn set_current_input(&self, rollup_input: &RollupInput) {
+            if let Some(metadata) = &rollup_input.data.metadata {
+                let manager = self.input_buffer_manager.lock().await;
+                manager.current_input.set(Some(InputRef::from(metadata)));
+            }
+        }
+        pub async fn current_input(&self) -> Option<InputRef> {
+            let manager = self.input_buffer_manager.lock().await;
+            manager.current_input.get()
+        }
+        pub async fn snapshot(&self) -> Snapshot {
+            let mut snapshot = Snapshot::default();
+            for consumer in &self.consumers {
+                let manager = consumer.input_buffer_manager.lock().await;
+                snapshot
+                    .consumers
+                    .insert(consumer.name.clone(), manager.snapshot());
+            }
+            snapshot
+        }
+        pub async fn restore(&self, snapshot: Snapshot) {
+            for (name, buffer) in snapshot.consumers {
+                match self.consumers.iter().find(|consumer| consumer.name == name) {
+                    Some(consumer) => {
+                        info!(
+                            "Restored {} inputs of consumer {}",
+                            buffer.messages.len(),
+                            name
+                        );
+                        consumer.input_buffer_manager.lock().await.restore(buffer);
+                    }
+                    None => warn!("Consumer {} of the snapshot is not registered", name),
+                }
+            }
+        }
         pub async fn set_inspecting(&self, value: bool) {
             let mut manager = self.input_buffer_manager.lock().await;
             manager.is_inspecting = value;
         }
         pub fn is_inspecting(&self) -> bool {
-            #[cfg(target_arch = "riscv64")]
-            {
-                let manager = match self.input_buffer_manager.try_lock() {
-                    Ok(manager) => manager,
-                    Err(_) => return false,
-                };
-                return manager.is_inspecting;
+            if self.mode == RunMode::Host {
+                return false;
+            }
+            match self.input_buffer_manager.try_lock() {
+                Ok(manager) => manager.is_inspecting,
+                Err(_) => false,
             }
-            false
+        }
+    }
+
+    impl Default for AppState {
+        fn default() -> Self {
+            Self::new()
         }
     }
 
@@ -307,14 +977,74 @@ pub mod structs {
                 flag_to_hold: Flag::new(),
                 request_count: Cell::new(0),
                 last_beacon: Cell::new(None),
+                fallback_beacons: HashMap::new(),
                 pending_beacon_timestamp: Cell::new(0),
                 randomness_salt: Cell::new(0),
+                current_input: Cell::new(None),
+                randomness_requests: HashMap::new(),
+                timelocks: HashMap::new(),
                 is_inspecting: false,
             }
         }
     }
 
     impl InputBufferManager {
+        pub fn snapshot(&self) -> InputBufferSnapshot {
+            let last_beacon = self.last_beacon.take();
+            self.last_beacon.set(last_beacon.clone());
+
+            InputBufferSnapshot {
+                messages: self
+                    .messages
+                    .iter()
+                    .map(|item| Item {
+                        request: item.request.clone(),
+                    })
+                    .collect(),
+                last_beacon,
+                fallback_beacons: self.fallback_beacons.clone(),
+                pending_beacon_timestamp: self.pending_beacon_timestamp.get(),
+                randomness_salt: self.randomness_salt.get(),
+                current_input: self.current_input.get(),
+                randomness_requests: self.randomness_requests.clone(),
+                timelocks: self
+                    .timelocks
+                    .values()
+                    .map(|timelock| TimelockSnapshot {
+                        id: timelock.id.clone(),
+                        round: timelock.round,
+                        ciphertext: timelock.ciphertext.clone(),
+                        plaintext: timelock.plaintext.clone(),
+                    })
+                    .collect(),
+            }
+        }
+
+        pub fn restore(&mut self, snapshot: InputBufferSnapshot) {
+            self.request_count.set(snapshot.messages.len());
+            self.messages = snapshot.messages;
+            self.last_beacon.set(snapshot.last_beacon);
+            self.fallback_beacons = snapshot.fallback_beacons;
+            self.pending_beacon_timestamp
+                .set(snapshot.pending_beacon_timestamp);
+            self.randomness_salt.set(snapshot.randomness_salt);
+            self.current_input.set(snapshot.current_input);
+            self.randomness_requests = snapshot.randomness_requests;
+            self.timelocks = snapshot
+                .timelocks
+                .into_iter()
+                .map(|timelock| {
+                    let timelock = TimelockCiphertext {
+                        id: timelock.id,
+                        round: timelock.round,
+                        ciphertext: timelock.ciphertext,
+                        plaintext: timelock.plaintext,
+                    };
+                    (timelock.id.clone(), timelock)
+                })
+                .collect();
+        }
+
         pub fn set_pending_beacon_timestamp(&mut self, timestamp: u64) {
             let current = self.pending_beacon_timestamp.take();
             // mantendo o mais recente para economizar transacoes
@@ -327,6 +1057,91 @@ pub mod structs {
             }
         }
 
+        /**
+         * Resolve every request waiting for this round or an earlier one, as long as the beacon
+         * was delivered by an input after the one that registered the request.
+         * The value comes from the derivation over the beacon, the consumer and the request id, with the beacon round as counter.
+         */
+        pub fn fulfil_randomness_requests(
+            &mut self,
+            drand_beacon: &DrandBeacon,
+            metadata: Option<&RollupInputDataMetadata>,
+            consumer: &str,
+            derivation: RandomnessDerivation,
+        ) {
+            let delivered_index = metadata.map(|metadata| metadata.input_index);
+            let consumer = match consumer {
+                DEFAULT_CONSUMER => None,
+                name => Some(consumer_namespace(name)),
+            };
+
+            self.randomness_requests
+                .values_mut()
+                .filter(|request| request.randomness.is_none())
+                .filter(|request| request.round <= drand_beacon.round)
+                .filter(|request| delivered_index.is_none_or(|index| request.input_index < index))
+                .for_each(|request| {
+                    let namespace = request_namespace(&request.id);
+                    let seed = [drand_beacon.randomness.as_str()]
+                        .into_iter()
+                        .chain(consumer.as_deref())
+                        .chain([namespace.as_str()])
+                        .collect::<Vec<_>>();
+                    match derivation.derive(&seed, drand_beacon.round, 1) {
+                        Some(value) => {
+                            info!(
+                                "Randomness request {} fulfilled by round {}",
+                                request.id, drand_beacon.round
+                            );
+                            request.randomness = Some(hex::encode(value));
+                            request.fulfilled_round = Some(drand_beacon.round);
+                        }
+                        None => warn!(
+                            "Cannot derive randomness request {} with {}",
+                            request.id,
+                            derivation.as_str()
+                        ),
+                    }
+                });
+        }
+
+        /**
+         * Rounds of the timelocks still sealed, only the beacon of that exact round opens them
+         */
+        pub fn pending_timelock_rounds(&self) -> impl Iterator<Item = u64> + '_ {
+            self.timelocks
+                .values()
+                .filter(|timelock| timelock.plaintext.is_none())
+                .map(|timelock| timelock.round)
+        }
+
+        /**
+         * Decrypt every timelock sealed until this round with the beacon signature.
+         */
+        pub fn open_timelocks(&mut self, drand_beacon: &DrandBeacon) {
+            let signature = match hex::decode(&drand_beacon.signature) {
+                Ok(signature) => signature,
+                Err(_) => return,
+            };
+
+            self.timelocks
+                .values_mut()
+                .filter(|timelock| timelock.plaintext.is_none())
+                .filter(|timelock| timelock.round == drand_beacon.round)
+                .for_each(
+                    |timelock| match decrypt_timelock(&timelock.ciphertext, &signature) {
+                        Ok(plaintext) => {
+                            info!(
+                                "Timelock {} opened by round {}",
+                                timelock.id, drand_beacon.round
+                            );
+                            timelock.plaintext = Some(format!("0x{}", hex::encode(plaintext)));
+                        }
+                        Err(e) => warn!("Could not open timelock {}: {}", timelock.id, e),
+                    },
+                );
+        }
+
         pub fn consume_input(&mut self) -> Option<Item> {
             info!("Consuming input");
             let buffer = self.messages.borrow_mut();
@@ -344,28 +1159,294 @@ pub mod structs {
 
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::sync::{atomic::AtomicU64, Arc, RwLock};
 
+    use proptest::{collection::vec, prelude::*};
+    use serde_json::json;
     use tokio::sync::Mutex;
 
-    use super::structs::{AppState, Beacon, DrandBeacon, InputBufferManager};
+    use crate::{
+        consumers::{route_input, DEFAULT_CONSUMER},
+        derivation::RandomnessDerivation,
+        rollup::input::{RollupInput, RollupInputDataMetadata},
+        utils::util::generate_payload_hex,
+    };
+
+    use super::structs::{
+        AppState, Beacon, ChainPolicy, Consumer, ConsumerRoute, DrandBeacon, DrandChain, DrandEnv,
+        DrandScheme, InputBufferManager, InputRef, RunMode, TimelockCiphertext,
+    };
 
     fn create_app_state() -> AppState {
         let version: Option<&str> = option_env!("CARGO_PKG_VERSION");
+        let manager = Arc::new(Mutex::new(InputBufferManager::default()));
         AppState {
-            input_buffer_manager: Arc::new(Mutex::new(InputBufferManager::default())),
-            drand_period: 3,
-            drand_genesis_time: 1677685200,
-            safe_seconds: 5,
+            input_buffer_manager: manager.clone(),
+            safe_seconds: Arc::new(AtomicU64::new(5)),
             version: version.unwrap_or("unknown").to_string(),
+            mode: RunMode::Test,
+            chains: Arc::new(RwLock::new(vec![DrandChain {
+                name: "primary".to_string(),
+                scheme: DrandScheme::default(),
+                public_key: String::new(),
+                period: 3,
+                genesis_time: 1677685200,
+            }])),
+            chain_policy: ChainPolicy::First,
+            derivation: RandomnessDerivation::default(),
+            consumer: DEFAULT_CONSUMER.to_string(),
+            consumers: vec![Consumer {
+                name: DEFAULT_CONSUMER.to_string(),
+                prefix: None,
+                input_buffer_manager: manager,
+            }],
+            consumer_route: ConsumerRoute::default(),
         }
     }
 
+    #[actix_web::test]
+    async fn test_app_state_is_inspecting_by_mode() {
+        let mut app = create_app_state();
+        assert!(!app.is_inspecting());
+
+        app.set_inspecting(true).await;
+        assert!(app.is_inspecting());
+
+        app.mode = RunMode::Host;
+        assert!(!app.is_inspecting());
+
+        app.mode = RunMode::Production;
+        assert!(app.is_inspecting());
+
+        app.set_inspecting(false).await;
+        assert!(!app.is_inspecting());
+    }
+
+    #[test]
+    fn test_run_mode_from_str() {
+        assert_eq!("production".parse(), Ok(RunMode::Production));
+        assert_eq!("Host".parse(), Ok(RunMode::Host));
+        assert_eq!("test".parse(), Ok(RunMode::Test));
+        assert!("riscv64".parse::<RunMode>().is_err());
+    }
+
+    #[test]
+    fn test_consumer_route_from_str() {
+        assert_eq!("prefix".parse(), Ok(ConsumerRoute::Prefix));
+        assert_eq!(
+            "field:game".parse(),
+            Ok(ConsumerRoute::Field("game".to_string()))
+        );
+        assert!("field:".parse::<ConsumerRoute>().is_err());
+        assert!("game".parse::<ConsumerRoute>().is_err());
+    }
+
+    #[test]
+    fn test_route_input_to_consumers() {
+        let consumer = |name: &str, prefix: Option<&str>| Consumer {
+            name: name.to_string(),
+            prefix: prefix.map(str::to_string),
+            input_buffer_manager: Default::default(),
+        };
+        let consumers = vec![
+            consumer(DEFAULT_CONSUMER, None),
+            consumer("poker", Some("01")),
+            consumer("blackjack", Some("0102")),
+        ];
+        let input = |payload: &str| {
+            RollupInput::builder()
+                .with_payload(payload.to_string())
+                .build()
+        };
+
+        let field = ConsumerRoute::Field("game".to_string());
+        let poker = generate_payload_hex(json!({"game": "poker", "input": "0x00"})).unwrap();
+        let dice = generate_payload_hex(json!({"game": "dice", "input": "0x00"})).unwrap();
+        assert_eq!(route_input(&consumers, &field, &input(&poker)), "poker");
+        assert_eq!(
+            route_input(&consumers, &field, &input(&dice)),
+            DEFAULT_CONSUMER
+        );
+        assert_eq!(
+            route_input(&consumers, &field, &input("0x0102")),
+            DEFAULT_CONSUMER
+        );
+
+        let prefix = ConsumerRoute::Prefix;
+        assert_eq!(route_input(&consumers, &prefix, &input("0x01ff")), "poker");
+        assert_eq!(
+            route_input(&consumers, &prefix, &input("0x0102ff")),
+            "blackjack"
+        );
+        assert_eq!(
+            route_input(&consumers, &prefix, &input("0x02")),
+            DEFAULT_CONSUMER
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_consumers_share_beacons_not_counters() {
+        let mut app = create_app_state();
+        app.consumers.push(Consumer {
+            name: "poker".to_string(),
+            prefix: None,
+            input_buffer_manager: Default::default(),
+        });
+        let poker = app.for_consumer("poker").unwrap();
+        assert!(app.for_consumer("dice").is_none());
+
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        let first = poker.get_randomness_for_timestamp(1677685300).unwrap();
+        let second = poker.get_randomness_for_timestamp(1677685300).unwrap();
+        let default = app.get_randomness_for_timestamp(1677685300).unwrap();
+
+        assert_ne!(first.value, second.value);
+        assert_ne!(first.value, default.value);
+        assert_eq!(
+            2,
+            poker
+                .input_buffer_manager
+                .lock()
+                .await
+                .randomness_salt
+                .get()
+        );
+        assert_eq!(
+            1,
+            app.input_buffer_manager.lock().await.randomness_salt.get()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_consumers_have_own_randomness_requests() {
+        let mut app = create_app_state();
+        app.consumers.push(Consumer {
+            name: "poker".to_string(),
+            prefix: None,
+            input_buffer_manager: Default::default(),
+        });
+        let poker = app.for_consumer("poker").unwrap();
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1677685230)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        for ctx in [&app, &poker] {
+            ctx.set_current_input(&input).await;
+            ctx.register_randomness_request("table-1").await.unwrap();
+        }
+        assert!(poker.register_randomness_request("table-1").await.is_err());
+
+        let later = RollupInputDataMetadata::builder()
+            .with_input_index(4)
+            .build();
+        let beacon = DrandBeacon::builder()
+            .with_round(12)
+            .with_randomness("12".to_string())
+            .build();
+        app.keep_newest_beacon(beacon, Some(&later)).await;
+
+        let default = app.get_randomness_request("table-1").await.unwrap();
+        let poker = poker.get_randomness_request("table-1").await.unwrap();
+        assert!(default.randomness.is_some());
+        assert!(poker.randomness.is_some());
+        assert_ne!(default.randomness, poker.randomness);
+    }
+
+    fn with_fallback_chain(mut app: AppState, policy: ChainPolicy) -> AppState {
+        app.chains.write().unwrap().push(DrandChain {
+            name: "mainnet".to_string(),
+            scheme: DrandScheme::PedersenChained,
+            public_key: String::new(),
+            period: 30,
+            genesis_time: 1595431050,
+        });
+        app.chain_policy = policy;
+        app
+    }
+
+    fn fallback_beacon(round: u64) -> DrandBeacon {
+        let mut beacon = DrandBeacon::builder().with_round(round).build();
+        beacon.randomness = "mainnet-randomness".to_string();
+        beacon.chain = Some("mainnet".to_string());
+        beacon
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_first_chain_policy_uses_fallback() {
+        let app = with_fallback_chain(create_app_state(), ChainPolicy::First);
+
+        // the primary chain stalled before the request time
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(2).build(), None)
+            .await;
+        assert!(app.get_randomness_for_timestamp(1677685300).is_none());
+
+        // mainnet round 2733823 is at 1677445740, still before the request
+        app.keep_newest_beacon(fallback_beacon(2733823), None).await;
+        assert!(app.get_randomness_for_timestamp(1677685300).is_none());
+        app.keep_newest_beacon(fallback_beacon(2741823), None).await;
+        let randomness = app.get_randomness_for_timestamp(1677685300).unwrap();
+        assert_eq!(randomness.chains, vec!["mainnet".to_string()]);
+
+        // once both reached the safe time the earliest beacon wins
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        let randomness = app.get_randomness_for_timestamp(1677685300).unwrap();
+        assert_eq!(randomness.chains, vec!["primary".to_string()]);
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_combine_chain_policy_needs_every_chain() {
+        let app = with_fallback_chain(create_app_state(), ChainPolicy::Combine);
+
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        assert!(app.get_randomness_for_timestamp(1677685300).is_none());
+
+        app.keep_newest_beacon(fallback_beacon(2741823), None).await;
+        let randomness = app.get_randomness_for_timestamp(1677685300).unwrap();
+        assert_eq!(
+            randomness.chains,
+            vec!["primary".to_string(), "mainnet".to_string()]
+        );
+
+        let manager = app.input_buffer_manager.lock().await;
+        assert_eq!(40, manager.last_beacon.take().unwrap().round);
+        assert_eq!(2741823, manager.fallback_beacons["mainnet"].round);
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_derivation_needs_hex_randomness() {
+        let mut app = with_fallback_chain(create_app_state(), ChainPolicy::Combine);
+        app.derivation = RandomnessDerivation::Blake3V1;
+
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        app.keep_newest_beacon(fallback_beacon(2741823), None).await;
+
+        // the fallback randomness isn't hex, so nothing is served and the counter stays
+        assert!(app.get_randomness_for_timestamp(1677685300).is_none());
+        assert_eq!(
+            0,
+            app.input_buffer_manager.lock().await.randomness_salt.get()
+        );
+
+        app.derivation = RandomnessDerivation::Sha3V1;
+        let randomness = app.get_randomness_for_timestamp(1677685300).unwrap();
+        assert_eq!(randomness.derivation, RandomnessDerivation::Sha3V1);
+        assert_eq!(
+            1,
+            app.input_buffer_manager.lock().await.randomness_salt.get()
+        );
+    }
+
     #[actix_web::test]
     async fn test_app_state_init_beacon() {
         let app = create_app_state();
         let beacon = DrandBeacon::builder().with_round(2).build();
-        app.keep_newest_beacon(beacon);
+        app.keep_newest_beacon(beacon, None).await;
         let manager = app.input_buffer_manager.lock().await;
         assert_eq!(2, manager.last_beacon.take().unwrap().round);
     }
@@ -379,11 +1460,12 @@ mod test {
                 timestamp: 1677685206,
                 round: 2,
                 randomness: "".to_string(),
+                delivered_in: None,
             }))
         }
         {
             let beacon = DrandBeacon::builder().with_round(1).build();
-            app.keep_newest_beacon(beacon);
+            app.keep_newest_beacon(beacon, None).await;
             let manager = app.input_buffer_manager.lock().await;
             assert_eq!(2, manager.last_beacon.take().unwrap().round);
         }
@@ -398,13 +1480,346 @@ mod test {
                 timestamp: 1677685206,
                 round: 2,
                 randomness: "".to_string(),
+                delivered_in: None,
             }))
         }
         {
             let beacon = DrandBeacon::builder().with_round(3).build();
-            app.keep_newest_beacon(beacon);
+            app.keep_newest_beacon(beacon, None).await;
             let manager = app.input_buffer_manager.lock().await;
             assert_eq!(3, manager.last_beacon.take().unwrap().round);
         }
     }
+
+    #[actix_web::test]
+    async fn test_app_state_keep_beacon_delivery() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(7)
+            .with_timestamp(1689949250)
+            .build();
+        let beacon = DrandBeacon::builder().with_round(2).build();
+        app.keep_newest_beacon(beacon, Some(&metadata)).await;
+        let manager = app.input_buffer_manager.lock().await;
+        let delivered_in = manager.last_beacon.take().unwrap().delivered_in;
+        assert_eq!(
+            Some(InputRef {
+                index: 7,
+                epoch_index: 0,
+                timestamp: 1689949250
+            }),
+            delivered_in
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_beacon_time_follows_the_chain_config() {
+        let app = with_fallback_chain(create_app_state(), ChainPolicy::First);
+        for chain in app.chains.write().unwrap().iter_mut() {
+            chain.period *= 10;
+        }
+
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        app.keep_newest_beacon(fallback_beacon(2741823), None).await;
+
+        let manager = app.input_buffer_manager.lock().await;
+        let primary = manager.last_beacon.take().unwrap();
+        assert_eq!(1677685200 + 40 * 30, primary.timestamp);
+        assert_eq!(
+            1595431050 + 2741823 * 300,
+            manager.fallback_beacons["mainnet"].timestamp
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_drand_config_update_changes_rounds() {
+        let mut app = create_app_state();
+        app.consumers.push(Consumer {
+            name: "poker".to_string(),
+            prefix: None,
+            input_buffer_manager: Default::default(),
+        });
+        let poker = app.for_consumer("poker").unwrap();
+
+        app.update_drand_config(&DrandEnv {
+            DRAND_PUBLIC_KEY: "83cf0f28".to_string(),
+            DRAND_PERIOD: Some(30),
+            DRAND_GENESIS_TIME: None,
+            DRAND_SAFE_SECONDS: Some(60),
+        });
+        assert_eq!("83cf0f28", app.primary_chain().unwrap().public_key);
+        assert_eq!(60, poker.safe_seconds());
+
+        // round 40 is now at 1677685200 + 1200 instead of 1677685200 + 120
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        assert!(poker
+            .get_randomness_for_timestamp(1677685200 + 1139)
+            .is_some());
+        assert!(app
+            .get_randomness_for_timestamp(1677685200 + 1140)
+            .is_none());
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1677685230)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        poker.set_current_input(&input).await;
+        let request = poker.register_randomness_request("table-1").await.unwrap();
+        assert_eq!(4, request.round);
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_ignore_beacon_from_same_input() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1689949250)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app.set_current_input(&input).await;
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1689949250)
+            .build();
+        let beacon = DrandBeacon::builder().with_round(2).build();
+        app.keep_newest_beacon(beacon, Some(&metadata)).await;
+
+        assert!(app.get_randomness_for_timestamp(1).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_snapshot_round_trip() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1677685200)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app.set_current_input(&input).await;
+        app.store_input(&input).await.unwrap();
+        app.register_randomness_request("dice").await.unwrap();
+        app.store_timelock(TimelockCiphertext {
+            id: "sealed".to_string(),
+            round: 99,
+            ciphertext: vec![1, 2, 3],
+            plaintext: None,
+        })
+        .await;
+        app.keep_newest_beacon(DrandBeacon::builder().with_round(40).build(), None)
+            .await;
+        assert!(app.get_randomness_for_timestamp(1677685300).is_some());
+
+        let snapshot = serde_json::to_value(app.snapshot().await).unwrap();
+        let restored = create_app_state();
+        restored
+            .restore(serde_json::from_value(snapshot.clone()).unwrap())
+            .await;
+
+        assert_eq!(
+            snapshot,
+            serde_json::to_value(restored.snapshot().await).unwrap()
+        );
+        assert_eq!(
+            app.get_randomness_for_timestamp(1677685300),
+            restored.get_randomness_for_timestamp(1677685300)
+        );
+        assert!(restored.consume_input().await.is_some());
+        assert_eq!(
+            restored.get_timelock("sealed").await.unwrap().ciphertext,
+            vec![1, 2, 3]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_use_beacon_from_later_input() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1689949250)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app.set_current_input(&input).await;
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(4)
+            .with_timestamp(1689949262)
+            .build();
+        let beacon = DrandBeacon::builder().with_round(2).build();
+        app.keep_newest_beacon(beacon, Some(&metadata)).await;
+
+        assert!(app.get_randomness_for_timestamp(1).is_some());
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_ignore_beacon_delivered_before_request() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(4)
+            .with_timestamp(1689949262)
+            .build();
+        let beacon = DrandBeacon::builder().with_round(4100000).build();
+        app.keep_newest_beacon(beacon, Some(&metadata)).await;
+
+        assert!(app.get_randomness_for_timestamp(1689949300).is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_register_randomness_request() {
+        let app = create_app_state();
+        assert!(app.register_randomness_request("table-1").await.is_err());
+
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1677685230)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app.set_current_input(&input).await;
+
+        let request = app.register_randomness_request("table-1").await.unwrap();
+        assert_eq!(3, request.input_index);
+        assert_eq!(12, request.round);
+        assert!(request.randomness.is_none());
+
+        assert!(app.register_randomness_request("table-1").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_app_state_fulfil_randomness_request_from_target_round() {
+        let app = create_app_state();
+        let metadata = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .with_timestamp(1677685230)
+            .build();
+        let input = RollupInput::builder().with_metadata(metadata).build();
+        app.set_current_input(&input).await;
+        app.register_randomness_request("table-1").await.unwrap();
+
+        let later = RollupInputDataMetadata::builder()
+            .with_input_index(4)
+            .build();
+        let beacon = DrandBeacon::builder()
+            .with_round(11)
+            .with_randomness("11".to_string())
+            .build();
+        app.keep_newest_beacon(beacon, Some(&later)).await;
+        let request = app.get_randomness_request("table-1").await.unwrap();
+        assert!(request.randomness.is_none());
+
+        let same = RollupInputDataMetadata::builder()
+            .with_input_index(3)
+            .build();
+        let beacon = DrandBeacon::builder()
+            .with_round(12)
+            .with_randomness("12".to_string())
+            .build();
+        app.keep_newest_beacon(beacon, Some(&same)).await;
+        let request = app.get_randomness_request("table-1").await.unwrap();
+        assert!(request.randomness.is_none());
+
+        // the relayer skipped past round 12, the first later round fulfils it
+        let beacon = DrandBeacon::builder()
+            .with_round(14)
+            .with_randomness("14".to_string())
+            .build();
+        app.keep_newest_beacon(beacon, Some(&later)).await;
+        let request = app.get_randomness_request("table-1").await.unwrap();
+        assert_eq!(12, request.round);
+        assert_eq!(Some(14), request.fulfilled_round);
+        assert!(request.randomness.is_some());
+
+        // a later delivery, even of the target round, cannot re-roll the request
+        for round in [12, 15] {
+            let beacon = DrandBeacon::builder()
+                .with_round(round)
+                .with_randomness("other".to_string())
+                .build();
+            app.keep_newest_beacon(beacon, Some(&later)).await;
+            let fulfilled = app.get_randomness_request("table-1").await.unwrap();
+            assert_eq!(request.randomness, fulfilled.randomness);
+            assert_eq!(Some(14), fulfilled.fulfilled_round);
+        }
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        actix_web::rt::System::new().block_on(future)
+    }
+
+    fn any_round() -> impl Strategy<Value = u64> {
+        prop_oneof![0..2_000_000u64, any::<u64>(), Just(u64::MAX)]
+    }
+
+    fn any_timestamp() -> impl Strategy<Value = u64> {
+        prop_oneof![
+            1_677_000_000..1_690_000_000u64,
+            any::<u64>(),
+            Just(u64::MAX)
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_keep_newest_beacon_never_goes_back(rounds in vec(any_round(), 1..20)) {
+            let app = create_app_state();
+            let kept = block_on(async {
+                let mut kept = vec![];
+                for round in &rounds {
+                    let beacon = DrandBeacon::builder().with_round(*round).build();
+                    app.keep_newest_beacon(beacon, None).await;
+                    let manager = app.input_buffer_manager.lock().await;
+                    let beacon = manager.last_beacon.take().unwrap();
+                    kept.push(beacon.round);
+                    manager.last_beacon.set(Some(beacon));
+                }
+                kept
+            });
+
+            prop_assert!(kept.windows(2).all(|pair| pair[0] <= pair[1]));
+            prop_assert_eq!(kept.last(), rounds.iter().max());
+        }
+
+        #[test]
+        fn prop_no_randomness_before_safe_window(round in any_round(), query_timestamp in any_timestamp()) {
+            let app = create_app_state();
+            block_on(app.keep_newest_beacon(DrandBeacon::builder().with_round(round).build(), None));
+
+            // round times of the primary chain of create_app_state
+            let beacon_time = round.saturating_mul(3).saturating_add(1677685200);
+            let safe_time = query_timestamp.saturating_add(app.safe_seconds());
+            let randomness = app.get_randomness_for_timestamp(query_timestamp);
+            prop_assert_eq!(randomness.is_some(), safe_time < beacon_time);
+        }
+
+        #[test]
+        fn prop_no_randomness_from_beacons_known_at_request(
+            request_index in 0..100u128,
+            delivered_index in 0..100u128,
+            round in 2_000..2_000_000u64,
+        ) {
+            let app = create_app_state();
+            let query_timestamp = 1677685200;
+            let metadata = |index| {
+                RollupInputDataMetadata::builder()
+                    .with_input_index(index)
+                    .with_timestamp(query_timestamp)
+                    .build()
+            };
+            let request = RollupInput::builder()
+                .with_metadata(metadata(request_index))
+                .build();
+
+            block_on(async {
+                app.set_current_input(&request).await;
+                let beacon = DrandBeacon::builder().with_round(round).build();
+                app.keep_newest_beacon(beacon, Some(&metadata(delivered_index)))
+                    .await;
+            });
+
+            let randomness = app.get_randomness_for_timestamp(query_timestamp);
+            prop_assert_eq!(randomness.is_some(), request_index < delivered_index);
+        }
+    }
 }
@@ -0,0 +1,63 @@
+use std::sync::OnceLock;
+
+use actix_web::App;
+use serde_json::Value;
+use utoipa::OpenApi;
+use utoipa_actix_web::AppExt;
+
+use crate::router::routes;
+
+#[derive(OpenApi)]
+#[openapi(info(
+    title = "cartesi-drand middleware",
+    description = "Sits between the DApp and the rollup server, buffers inputs and answers randomness from verified drand beacons."
+))]
+struct ApiDoc;
+
+/**
+ * Doc comments here are written as /** */ blocks, keep their text without the leading asterisks
+ */
+fn tidy_descriptions(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(text) if key == "summary" || key == "description" => {
+                        *text = text
+                            .lines()
+                            .map(|line| line.trim().trim_start_matches('*').trim())
+                            .filter(|line| !line.is_empty())
+                            .collect::<Vec<_>>()
+                            .join("\n");
+                    }
+                    value => tidy_descriptions(value),
+                }
+            }
+        }
+        Value::Array(list) => list.iter_mut().for_each(tidy_descriptions),
+        _ => {}
+    }
+}
+
+/**
+ * OpenAPI 3 description of the middleware, served at /openapi.json.
+ * Paths are collected from the same routes::configure the server runs and schemas from the serde models.
+ */
+pub fn openapi_document() -> Value {
+    static DOCUMENT: OnceLock<Value> = OnceLock::new();
+    DOCUMENT
+        .get_or_init(|| {
+            let (_, mut document) = App::new()
+                .into_utoipa_app()
+                .openapi(ApiDoc::openapi())
+                .configure(routes::configure)
+                .split_for_parts();
+            // the crate has no license to advertise
+            document.info.license = None;
+
+            let mut document = serde_json::to_value(document).unwrap_or_default();
+            tidy_descriptions(&mut document);
+            document
+        })
+        .clone()
+}
@@ -0,0 +1,107 @@
+use std::{
+    error::Error,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use actix_web::{
+    body::{to_bytes, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web::Bytes,
+};
+use dotenvy::var;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+
+/**
+ * One line of the recording, either an exchange with the rollup server or a call from the DApp.
+ * Bodies are kept as JSON when they parse, otherwise as a string.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Interaction {
+    Rollup {
+        path: String,
+        request: Value,
+        status: u16,
+        response: Value,
+    },
+    Dapp {
+        method: String,
+        path: String,
+        request: Value,
+        status: u16,
+        response: String,
+    },
+}
+
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
+/**
+ * Recording is on when RECORD_FILE points to the JSONL file to append to
+ */
+pub fn record_file() -> Option<PathBuf> {
+    var("RECORD_FILE").ok().map(PathBuf::from)
+}
+
+pub fn append_interaction(path: &Path, interaction: &Interaction) -> Result<(), Box<dyn Error>> {
+    let line = serde_json::to_string(interaction)?;
+    let _guard = RECORD_LOCK.lock().map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+pub fn record(interaction: Interaction) {
+    if let Some(path) = record_file() {
+        if let Err(e) = append_interaction(&path, &interaction) {
+            error!("Error recording interaction: {}", e);
+        }
+    }
+}
+
+pub fn body_to_value(body: &[u8]) -> Value {
+    if body.is_empty() {
+        return Value::Null;
+    }
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).to_string()))
+}
+
+/**
+ * Record every DApp call with its response when RECORD_FILE is set
+ */
+pub async fn record_dapp_calls(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    if record_file().is_none() {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let method = req.method().to_string();
+    let path = req.uri().to_string();
+    let request = req.extract::<Bytes>().await?;
+    req.set_payload(request.clone().into());
+
+    let res = next.call(req).await?;
+    let status = res.status().as_u16();
+    let (http_req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let body = to_bytes(body).await.map_err(Into::into)?;
+
+    record(Interaction::Dapp {
+        method,
+        path,
+        request: body_to_value(&request),
+        status,
+        response: String::from_utf8_lossy(&body).to_string(),
+    });
+
+    let res = res.set_body(body).map_into_boxed_body();
+    Ok(ServiceResponse::new(http_req, res))
+}
@@ -1,11 +1,12 @@
 pub mod server {
     use dotenvy::var;
     use hyper::{Body, Response};
-    use log::info;
     use serde_json::{json, Value};
     use std::error::Error;
+    use tracing::info;
 
     use super::input::RollupInput;
+    use crate::recorder::{record, record_file, Interaction};
 
     pub async fn send_finish(status: &str) -> Result<Response<Body>, Box<dyn Error>> {
         let server_str = var("ROLLUP_HTTP_SERVER_URL").expect("Env is not set");
@@ -31,13 +32,29 @@ pub mod server {
         status: &str,
     ) -> Result<RollupInput, Box<dyn Error>> {
         let response = send_finish(status).await?;
-
-        if response.status() == hyper::StatusCode::ACCEPTED {
+        let response_status = response.status();
+
+        if response_status == hyper::StatusCode::ACCEPTED {
+            record(Interaction::Rollup {
+                path: "/finish".to_string(),
+                request: json!({ "status": status }),
+                status: response_status.as_u16(),
+                response: Value::Null,
+            });
             return Err("Skip".into());
         }
 
         let result = RollupInput::try_from_async(response).await?;
 
+        if record_file().is_some() {
+            record(Interaction::Rollup {
+                path: "/finish".to_string(),
+                request: json!({ "status": status }),
+                status: response_status.as_u16(),
+                response: serde_json::to_value(&result)?,
+            });
+        }
+
         Ok(result)
     }
 
@@ -50,9 +67,37 @@ pub mod server {
             .uri(format!("{}/report", server_addr))
             .body(hyper::Body::from(report.to_string()))?;
 
-        let _ = client.request(req).await?;
+        let response = client.request(req).await?;
+        record(Interaction::Rollup {
+            path: "/report".to_string(),
+            request: report,
+            status: response.status().as_u16(),
+            response: Value::Null,
+        });
         Ok("accept")
     }
+
+    pub async fn send_notice(notice: Value) -> Result<(), Box<dyn std::error::Error>> {
+        let server_addr = var("ROLLUP_HTTP_SERVER_URL").expect("ROLLUP_HTTP_SERVER_URL is not set");
+        let client = hyper::Client::new();
+        let req = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .uri(format!("{}/notice", server_addr))
+            .body(hyper::Body::from(notice.to_string()))?;
+
+        let response = client.request(req).await?;
+        record(Interaction::Rollup {
+            path: "/notice".to_string(),
+            request: notice,
+            status: response.status().as_u16(),
+            response: Value::Null,
+        });
+        if !response.status().is_success() {
+            return Err(format!("Notice rejected with status {}", response.status()).into());
+        }
+        Ok(())
+    }
 }
 
 pub mod input {
@@ -64,6 +109,7 @@ pub mod input {
     use serde::{Deserialize, Serialize};
     use serde_json::json;
     use std::error::Error;
+    use utoipa::ToSchema;
 
     #[derive(Default, Debug)]
     pub enum RollupState {
@@ -112,15 +158,18 @@ pub mod input {
         }
     }
 
-    #[derive(Serialize, Deserialize, Debug, Default)]
+    #[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
     pub struct RollupInput {
         pub data: RollupInputData,
+        #[schema(value_type = String, example = "advance_state")]
         pub request_type: RollupState,
     }
 
+    #[cfg(test)]
     #[derive(Default)]
     pub struct RollupInputBuilder(RollupInput);
 
+    #[cfg(test)]
     impl RollupInputBuilder {
         pub fn with_payload(mut self, payload: String) -> Self {
             self.0.data.payload = payload;
@@ -168,6 +217,7 @@ pub mod input {
             Ok(result_deserialization)
         }
 
+        #[cfg(test)]
         pub fn builder() -> RollupInputBuilder {
             RollupInputBuilder::default()
         }
@@ -179,8 +229,11 @@ pub mod input {
         }
     }
 
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, ToSchema)]
     pub struct RollupInputData {
+        /**
+         * 0x hex encoded payload
+         */
         pub payload: String,
         pub metadata: Option<RollupInputDataMetadata>,
     }
@@ -198,7 +251,7 @@ pub mod input {
 
     type BigIntLike = u128;
 
-    #[derive(Serialize, Deserialize, Debug, Default)]
+    #[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
     pub struct RollupInputDataMetadata {
         pub block_number: BigIntLike,
         pub epoch_index: BigIntLike,
@@ -207,15 +260,18 @@ pub mod input {
         pub timestamp: u64,
     }
 
+    #[cfg(test)]
     impl RollupInputDataMetadata {
         pub fn builder() -> RollupInputDataMetadataBuilder {
             RollupInputDataMetadataBuilder::default()
         }
     }
 
+    #[cfg(test)]
     #[derive(Default)]
     pub struct RollupInputDataMetadataBuilder(RollupInputDataMetadata);
 
+    #[cfg(test)]
     impl RollupInputDataMetadataBuilder {
         pub fn with_block_number(mut self, block_number: BigIntLike) -> Self {
             self.0.block_number = block_number;
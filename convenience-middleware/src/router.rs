@@ -1,18 +1,58 @@
 pub mod routes {
-    use actix_web::{get, post, put, web, HttpResponse, Responder, ResponseError};
-    use log::{error, info};
+    use actix_web::{
+        get, post, put, web, HttpResponse, HttpResponseBuilder, Responder, ResponseError,
+    };
+    use tracing::{error, info, warn, Instrument};
+    use utoipa_actix_web::service_config::ServiceConfig;
 
     use crate::{
-        drand::{get_drand_beacon, is_querying_pending_beacon, send_pending_beacon_report},
+        drand::{
+            apply_drand_config_update, get_drand_beacon, get_drand_config_update,
+            is_querying_pending_beacon, send_pending_beacon_report,
+        },
         errors::CheckerError,
-        models::structs::{AppState, DrandEnv, RequestRollups, Timestamp},
+        models::structs::{
+            AppState, DrandEnv, InputRef, Randomness, RandomnessRequest, RandomnessRequestId,
+            RequestRollups, TimelockCiphertext, Timestamp,
+        },
+        openapi::openapi_document,
         rollup::{
             input::{has_input_inside_input, RollupInput},
             server::send_finish_and_retrieve_input,
         },
-        utils::util::{load_env_from_memory, write_env_to_json},
+        telemetry::{correlation_id, input_span, CORRELATION_ID_HEADER},
+        tlock::get_timelock,
+        utils::util::{is_drand_config_endpoint_enabled, store_drand_config},
     };
 
+    pub const DRAND_CHAINS_HEADER: &str = "X-Drand-Chains";
+    pub const RANDOMNESS_DERIVATION_HEADER: &str = "X-Randomness-Derivation";
+
+    /**
+     * 200 response carrying the correlation id of the input handed to the DApp
+     */
+    fn dispatch_input(input: &RollupInput) -> HttpResponseBuilder {
+        let mut response = HttpResponse::Ok();
+        if let Some(metadata) = &input.data.metadata {
+            response.insert_header((
+                CORRELATION_ID_HEADER,
+                correlation_id(metadata.epoch_index, metadata.input_index),
+            ));
+        }
+        response
+    }
+
+    /**
+     * Change the drand config, disabled unless ENABLE_DRAND_CONFIG_ENDPOINT=true
+     */
+    #[utoipa::path(
+        request_body = DrandEnv,
+        responses(
+            (status = 204, description = "Config applied"),
+            (status = 400, description = "Config could not be stored", body = CheckerError),
+            (status = 403, description = "Endpoint disabled", body = CheckerError),
+        )
+    )]
     #[put("/update_drand_config")]
     async fn update_drand_config(
         ctx: web::Data<AppState>,
@@ -23,11 +63,16 @@ pub mod routes {
             ctx.version
         );
 
+        if !is_drand_config_endpoint_enabled() {
+            warn!("update_drand_config is disabled, send a drand_config_update input instead");
+            return Err(CheckerError::DrandConfigEndpointDisabled);
+        }
+
         let _ = ctx.input_buffer_manager.lock().await;
 
         let drand = body.into_inner();
 
-        let result = write_env_to_json().await;
+        let result = store_drand_config(drand.clone()).await;
 
         if let Err(e) = result {
             error!("Error updating drand config: {}", e);
@@ -35,12 +80,21 @@ pub mod routes {
                 cause: e.to_string(),
             });
         }
-
-        load_env_from_memory(drand).await;
+        ctx.update_drand_config(&drand);
 
         Ok(HttpResponse::NoContent().finish())
     }
 
+    /**
+     * Replaces the rollup server /finish for the DApp
+     */
+    #[utoipa::path(
+        request_body = RequestRollups,
+        responses(
+            (status = 200, description = "Next input for the DApp", body = RollupInput),
+            (status = 202, description = "No input for the DApp, beacons and middleware inspects are consumed here"),
+        )
+    )]
     #[post("/finish")]
     async fn consume_buffer(
         ctx: web::Data<AppState>,
@@ -50,14 +104,50 @@ pub mod routes {
             "Received finish request from DApp {:?} version={}",
             body, ctx.version
         );
+        finish(&ctx).await
+    }
 
+    /**
+     * Replaces the rollup server /finish for a consumer registered in CONSUMERS
+     */
+    #[utoipa::path(
+        params(("name" = String, Path, description = "Consumer registered in CONSUMERS")),
+        request_body = RequestRollups,
+        responses(
+            (status = 200, description = "Next input for the consumer", body = RollupInput),
+            (status = 202, description = "No input for the consumer, beacons and middleware inspects are consumed here"),
+            (status = 404, description = "Unknown consumer", body = CheckerError),
+        )
+    )]
+    #[post("/consumers/{name}/finish")]
+    async fn consume_consumer_buffer(
+        ctx: web::Data<AppState>,
+        name: web::Path<String>,
+        body: web::Json<RequestRollups>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received finish request from consumer {} {:?} version={}",
+            name, body, ctx.version
+        );
+        let ctx = ctx
+            .for_consumer(&name)
+            .ok_or(CheckerError::UnknownConsumer)?;
+        Ok::<_, CheckerError>(finish(&ctx).await)
+    }
+
+    /**
+     * Next input of the consumer's queue, otherwise the next input from the rollup server.
+     * Inputs routed to another consumer are queued for it.
+     */
+    async fn finish(ctx: &AppState) -> HttpResponse {
         // the DApp consume from the buffer first
         if let Some(item) = ctx.consume_input().await {
             let request = item.request.clone();
             match RollupInput::try_from(item) {
                 Ok(input) => {
                     if has_input_inside_input(&input) {
-                        return HttpResponse::Ok().body(request);
+                        ctx.set_current_input(&input).await;
+                        return dispatch_input(&input).body(request);
                     } else {
                         return HttpResponse::Accepted().finish();
                     }
@@ -70,36 +160,99 @@ pub mod routes {
             Ok(input) => input,
             Err(_) => return HttpResponse::Accepted().finish(),
         };
-        match rollup_input.request_type.as_str() {
-            "advance_state" => {
-                ctx.set_inspecting(false).await;
-                if let Ok(beacon) = get_drand_beacon(&rollup_input.data.payload) {
-                    info!("Is Drand!!! {:?}", beacon);
-                    ctx.keep_newest_beacon(beacon);
+        let span = input_span(&rollup_input);
+        let target = ctx.for_input(&rollup_input);
+
+        async move {
+            match rollup_input.request_type.as_str() {
+                "advance_state" => {
+                    ctx.set_inspecting(false).await;
+                    if let Ok(drand) = get_drand_config_update(&rollup_input) {
+                        apply_drand_config_update(ctx, &rollup_input, drand).await;
+                    }
+                    if let Ok(timelock) = get_timelock(&rollup_input.data.payload) {
+                        ctx.store_timelock(timelock).await;
+                    }
+                    if let Ok(beacon) =
+                        get_drand_beacon(&rollup_input.data.payload, &ctx.drand_chains())
+                    {
+                        info!("Is Drand!!! {:?}", beacon);
+                        ctx.keep_newest_beacon(beacon, rollup_input.data.metadata.as_ref())
+                            .await;
+                    }
+                    if has_input_inside_input(&rollup_input) && target.consumer == ctx.consumer {
+                        ctx.set_current_input(&rollup_input).await;
+                    }
                 }
-            }
-            "inspect_state" => {
-                ctx.set_inspecting(true).await;
-                if is_querying_pending_beacon(&rollup_input).unwrap() {
-                    send_pending_beacon_report(&ctx).await;
+                "inspect_state" => {
+                    ctx.set_inspecting(true).await;
+                    if is_querying_pending_beacon(&rollup_input).unwrap_or(false) {
+                        send_pending_beacon_report(ctx).await;
 
-                    // This is a specific inspect, so we omit it from the DApp
-                    return HttpResponse::Accepted().finish();
+                        // This is a specific inspect, so we omit it from the DApp
+                        return HttpResponse::Accepted().finish();
+                    }
                 }
+                &_ => {
+                    error!("Unknown request type");
+                }
+            };
+
+            if target.consumer != ctx.consumer {
+                info!("Input queued for consumer {}", target.consumer);
+                if let Err(e) = target.store_input(&rollup_input).await {
+                    error!("Error storing input: {}", e);
+                }
+                return HttpResponse::Accepted().finish();
             }
-            &_ => {
-                error!("Unknown request type");
+
+            // Dispatch the input to the DApp
+            if has_input_inside_input(&rollup_input) {
+                dispatch_input(&rollup_input).json(rollup_input)
+            } else {
+                HttpResponse::Accepted().finish()
             }
-        };
+        }
+        .instrument(span)
+        .await
+    }
 
-        // Dispatch the input to the DApp
-        if has_input_inside_input(&rollup_input) {
-            HttpResponse::Ok().json(rollup_input)
-        } else {
-            HttpResponse::Accepted().finish()
+    /**
+     * The body is the seed, the X-Drand-Chains header lists the chains that produced it,
+     * X-Randomness-Derivation the derivation version and X-Correlation-Id the input asking for it
+     */
+    fn randomness_response(randomness: Randomness, input: Option<InputRef>) -> HttpResponse {
+        let mut response = HttpResponse::Ok();
+        response.insert_header((DRAND_CHAINS_HEADER, randomness.chains.join(",")));
+        response.insert_header((RANDOMNESS_DERIVATION_HEADER, randomness.derivation.as_str()));
+        if let Some(input) = input {
+            response.insert_header((
+                CORRELATION_ID_HEADER,
+                correlation_id(input.epoch_index, input.index),
+            ));
         }
+        response.body(randomness.value)
     }
 
+    /**
+     * Seed from the first beacon after timestamp plus the safe seconds
+     */
+    #[utoipa::path(
+        params(Timestamp),
+        responses(
+            (
+                status = 200,
+                description = "Hex encoded seed, 32 bytes per output",
+                body = String,
+                content_type = "text/plain",
+                headers(
+                    ("X-Drand-Chains" = String, description = "Comma separated drand chains used for the seed"),
+                    ("X-Randomness-Derivation" = String, description = "Derivation version of the seed"),
+                )
+            ),
+            (status = 400, description = "No beacon yet, inspecting, the input was buffered, the beacon is invalid or the derivation cannot produce the outputs", body = CheckerError),
+        )
+    )]
     #[get("/random")]
     async fn request_random(
         ctx: web::Data<AppState>,
@@ -109,10 +262,61 @@ pub mod routes {
             "Received random request from DApp timestamp={} version={}",
             query.timestamp, ctx.version
         );
-        let randomness: Option<String> = ctx.get_randomness_for_timestamp(query.timestamp);
+        random(&ctx, &query).await
+    }
+
+    /**
+     * Seed for a consumer registered in CONSUMERS
+     */
+    #[utoipa::path(
+        params(("name" = String, Path, description = "Consumer registered in CONSUMERS"), Timestamp),
+        responses(
+            (
+                status = 200,
+                description = "Hex encoded seed, 32 bytes per output",
+                body = String,
+                content_type = "text/plain",
+                headers(
+                    ("X-Drand-Chains" = String, description = "Comma separated drand chains used for the seed"),
+                    ("X-Randomness-Derivation" = String, description = "Derivation version of the seed"),
+                )
+            ),
+            (status = 400, description = "No beacon yet, inspecting, the input was buffered, the beacon is invalid or the derivation cannot produce the outputs", body = CheckerError),
+            (status = 404, description = "Unknown consumer", body = CheckerError),
+        )
+    )]
+    #[get("/consumers/{name}/random")]
+    async fn request_consumer_random(
+        ctx: web::Data<AppState>,
+        name: web::Path<String>,
+        query: web::Query<Timestamp>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received random request from consumer {} timestamp={} version={}",
+            name, query.timestamp, ctx.version
+        );
+        let ctx = ctx
+            .for_consumer(&name)
+            .ok_or(CheckerError::UnknownConsumer)?;
+        random(&ctx, &query).await
+    }
+
+    /**
+     * Seed for the consumer, otherwise halt until the rollup server delivers a usable beacon
+     */
+    async fn random(ctx: &AppState, query: &Timestamp) -> Result<HttpResponse, CheckerError> {
+        let outputs = query.outputs();
+        if !ctx.derivation.supports_outputs(outputs) {
+            return Err(CheckerError::UnsupportedRandomnessOutputs {
+                derivation: ctx.derivation.as_str(),
+                outputs,
+            });
+        }
+        let randomness: Option<Randomness> =
+            ctx.get_randomness_outputs_for_timestamp(query.timestamp, outputs);
         if let Some(randomness) = randomness {
             // we already have the randomness to continue the process
-            return Ok(HttpResponse::Ok().body(randomness));
+            return Ok(randomness_response(randomness, ctx.current_input().await));
         }
         if ctx.is_inspecting() {
             info!("When inspecting we does not call finish from /random endpoint.");
@@ -126,50 +330,261 @@ pub mod routes {
                 return Err(CheckerError::SendRollupAndRetrieveInputError);
             }
         };
-        match rollup_input.request_type.as_str() {
-            "advance_state" => {
-                ctx.set_inspecting(false).await;
-                // Store the input in the buffer, so that it can be accessed from the /finish endpoint.
-                let err = ctx.store_input(&rollup_input).await;
+        let span = input_span(&rollup_input);
 
-                if let Err(e) = err {
-                    error!("Error storing input: {}", e);
-                    return Err(CheckerError::StoreInputError);
-                }
+        async move {
+            match rollup_input.request_type.as_str() {
+                "advance_state" => {
+                    ctx.set_inspecting(false).await;
+                    // Store the input in the buffer, so that it can be accessed from the /finish endpoint.
+                    let err = ctx
+                        .for_input(&rollup_input)
+                        .store_input(&rollup_input)
+                        .await;
 
-                match get_drand_beacon(&rollup_input.data.payload) {
-                    Ok(beacon) => {
-                        info!("Is Drand!!! {:?}", beacon);
-                        ctx.keep_newest_beacon(beacon);
-                        let randomness = ctx.get_randomness_for_timestamp(query.timestamp);
-                        if let Some(randomness) = randomness {
-                            return Ok(HttpResponse::Ok().body(randomness));
+                    if let Err(e) = err {
+                        error!("Error storing input: {}", e);
+                        return Err(CheckerError::StoreInputError);
+                    }
+
+                    if let Ok(drand) = get_drand_config_update(&rollup_input) {
+                        apply_drand_config_update(ctx, &rollup_input, drand).await;
+                    }
+                    if let Ok(timelock) = get_timelock(&rollup_input.data.payload) {
+                        ctx.store_timelock(timelock).await;
+                    }
+
+                    match get_drand_beacon(&rollup_input.data.payload, &ctx.drand_chains()) {
+                        Ok(beacon) => {
+                            info!("Is Drand!!! {:?}", beacon);
+                            ctx.keep_newest_beacon(beacon, rollup_input.data.metadata.as_ref())
+                                .await;
+                            let randomness =
+                                ctx.get_randomness_outputs_for_timestamp(query.timestamp, outputs);
+                            if let Some(randomness) = randomness {
+                                let input = ctx.current_input().await;
+                                return Ok(randomness_response(randomness, input));
+                            }
+                            Err(CheckerError::RandomnessError)
+                        }
+                        Err(e) => {
+                            error!("Error getting randomness: {}", e);
+                            Err(CheckerError::SignatureErrorBeacon)
                         }
-                        Err(CheckerError::RandomnessError)
                     }
-                    Err(e) => {
-                        error!("Error getting randomness: {}", e);
-                        Err(CheckerError::SignatureErrorBeacon)
+                }
+                "inspect_state" => {
+                    ctx.set_inspecting(true).await;
+                    if is_querying_pending_beacon(&rollup_input).unwrap_or(false) {
+                        send_pending_beacon_report(ctx).await;
+
+                        // This is a specific inspect, so we omit it from the DApp
+                        Err(CheckerError::ByPassInspect)
+                    } else {
+                        // Store the input in the buffer, so that it can be accessed from the /finish endpoint.
+                        let target = ctx.for_input(&rollup_input);
+                        if let Err(e) = target.store_input(&rollup_input).await {
+                            error!("Error storing input: {}", e);
+                            return Err(CheckerError::StoreInputError);
+                        }
+                        Err(CheckerError::StoreInputByPass)
                     }
                 }
-            }
-            "inspect_state" => {
-                ctx.set_inspecting(true).await;
-                if is_querying_pending_beacon(&rollup_input).unwrap() {
-                    send_pending_beacon_report(&ctx).await;
-
-                    // This is a specific inspect, so we omit it from the DApp
-                    Err(CheckerError::ByPassInspect)
-                } else {
-                    // Store the input in the buffer, so that it can be accessed from the /finish endpoint.
-                    ctx.store_input(&rollup_input).await;
-                    Err(CheckerError::StoreInputByPass)
+                &_ => {
+                    error!("Unknown request type");
+                    Err(CheckerError::UnknownRequestType)
                 }
             }
-            &_ => {
-                error!("Unknown request type");
-                Err(CheckerError::UnknownRequestType)
-            }
         }
+        .instrument(span)
+        .await
+    }
+
+    /**
+     * Bind a randomness request to the first round after the current input
+     */
+    #[utoipa::path(
+        request_body = RandomnessRequestId,
+        responses(
+            (status = 200, description = "Registered request", body = RandomnessRequest),
+            (status = 400, description = "No input being processed or duplicated id", body = CheckerError),
+        )
+    )]
+    #[post("/randomness_request")]
+    async fn register_randomness_request(
+        ctx: web::Data<AppState>,
+        body: web::Json<RandomnessRequestId>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received randomness request {} from DApp version={}",
+            body.id, ctx.version
+        );
+
+        ctx.register_randomness_request(&body.id)
+            .await
+            .map(|request| HttpResponse::Ok().json(request))
+    }
+
+    /**
+     * Bind a randomness request of a consumer registered in CONSUMERS
+     */
+    #[utoipa::path(
+        params(("name" = String, Path, description = "Consumer registered in CONSUMERS")),
+        request_body = RandomnessRequestId,
+        responses(
+            (status = 200, description = "Registered request", body = RandomnessRequest),
+            (status = 400, description = "No input being processed or duplicated id", body = CheckerError),
+            (status = 404, description = "Unknown consumer", body = CheckerError),
+        )
+    )]
+    #[post("/consumers/{name}/randomness_request")]
+    async fn register_consumer_randomness_request(
+        ctx: web::Data<AppState>,
+        name: web::Path<String>,
+        body: web::Json<RandomnessRequestId>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received randomness request {} from consumer {} version={}",
+            body.id, name, ctx.version
+        );
+        let ctx = ctx
+            .for_consumer(&name)
+            .ok_or(CheckerError::UnknownConsumer)?;
+
+        ctx.register_randomness_request(&body.id)
+            .await
+            .map(|request| HttpResponse::Ok().json(request))
+    }
+
+    /**
+     * Randomness of a request once its round arrived
+     */
+    #[utoipa::path(
+        params(("id" = String, Path, description = "Id the request was registered with")),
+        responses(
+            (status = 200, description = "Fulfilled request", body = RandomnessRequest),
+            (status = 400, description = "Waiting for the round", body = CheckerError),
+            (status = 404, description = "Unknown request", body = CheckerError),
+        )
+    )]
+    #[get("/randomness_request/{id}")]
+    async fn fulfil_randomness_request(
+        ctx: web::Data<AppState>,
+        id: web::Path<String>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received randomness fulfilment {} from DApp version={}",
+            id, ctx.version
+        );
+        randomness_request(&ctx, &id).await
+    }
+
+    /**
+     * Randomness of a consumer request once its round arrived
+     */
+    #[utoipa::path(
+        params(
+            ("name" = String, Path, description = "Consumer registered in CONSUMERS"),
+            ("id" = String, Path, description = "Id the request was registered with"),
+        ),
+        responses(
+            (status = 200, description = "Fulfilled request", body = RandomnessRequest),
+            (status = 400, description = "Waiting for the round", body = CheckerError),
+            (status = 404, description = "Unknown consumer or request", body = CheckerError),
+        )
+    )]
+    #[get("/consumers/{name}/randomness_request/{id}")]
+    async fn fulfil_consumer_randomness_request(
+        ctx: web::Data<AppState>,
+        path: web::Path<(String, String)>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        let (name, id) = path.into_inner();
+        info!(
+            "Received randomness fulfilment {} from consumer {} version={}",
+            id, name, ctx.version
+        );
+        let ctx = ctx
+            .for_consumer(&name)
+            .ok_or(CheckerError::UnknownConsumer)?;
+        randomness_request(&ctx, &id).await
+    }
+
+    /**
+     * The request of the consumer, only once the beacon of its round fulfilled it
+     */
+    async fn randomness_request(ctx: &AppState, id: &str) -> Result<HttpResponse, CheckerError> {
+        let request = ctx
+            .get_randomness_request(id)
+            .await
+            .ok_or(CheckerError::UnknownRandomnessRequest)?;
+
+        match request.randomness {
+            Some(_) => Ok(HttpResponse::Ok().json(request)),
+            None => Err(CheckerError::PendingRandomnessRequest {
+                round: request.round,
+            }),
+        }
+    }
+
+    /**
+     * Plaintext of a timelock once its round arrived
+     */
+    #[utoipa::path(
+        params(("id" = String, Path, description = "Id of the timelock input")),
+        responses(
+            (status = 200, description = "Opened timelock", body = TimelockCiphertext),
+            (status = 400, description = "Sealed until its round", body = CheckerError),
+            (status = 404, description = "Unknown timelock", body = CheckerError),
+        )
+    )]
+    #[get("/timelock/{id}")]
+    async fn open_timelock(
+        ctx: web::Data<AppState>,
+        id: web::Path<String>,
+    ) -> Result<impl Responder, impl ResponseError> {
+        info!(
+            "Received timelock request {} from DApp version={}",
+            id, ctx.version
+        );
+
+        let timelock = ctx
+            .get_timelock(&id)
+            .await
+            .ok_or(CheckerError::UnknownTimelock)?;
+
+        match timelock.plaintext {
+            Some(_) => Ok(HttpResponse::Ok().json(timelock)),
+            None => Err(CheckerError::SealedTimelock {
+                round: timelock.round,
+            }),
+        }
+    }
+
+    /**
+     * This document
+     */
+    #[utoipa::path(responses((status = 200, description = "OpenAPI document", body = Object)))]
+    #[get("/openapi.json")]
+    async fn openapi() -> impl Responder {
+        HttpResponse::Ok().json(openapi_document())
+    }
+
+    /**
+     * Every route of the middleware. The app and /openapi.json are built from these same calls,
+     * so a route cannot be served without being documented.
+     */
+    pub fn configure(config: &mut ServiceConfig) {
+        config
+            .service(request_random)
+            .service(consume_buffer)
+            .service(request_consumer_random)
+            .service(consume_consumer_buffer)
+            .service(update_drand_config)
+            .service(register_randomness_request)
+            .service(fulfil_randomness_request)
+            .service(register_consumer_randomness_request)
+            .service(fulfil_consumer_randomness_request)
+            .service(open_timelock)
+            .service(openapi);
     }
 }
@@ -0,0 +1,98 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web,
+};
+use dotenvy::var;
+use tracing::{field, info_span, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+
+use crate::{consumers::DEFAULT_CONSUMER, models::structs::AppState, rollup::input::RollupInput};
+
+/**
+ * Header with the epoch and input index of the input the DApp is handling, so its logs can join ours
+ */
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
+/**
+ * Log level comes from RUST_LOG (info by default), LOG_FORMAT=json switches to JSON lines with the span fields
+ */
+pub fn init_tracing() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).try_init(),
+        _ => builder.without_time().try_init(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Logger already set: {}", e);
+    }
+}
+
+pub fn correlation_id(epoch_index: u128, input_index: u128) -> String {
+    format!("{}-{}", epoch_index, input_index)
+}
+
+/**
+ * Span around everything done for an input received from the rollup server
+ */
+pub fn input_span(input: &RollupInput) -> Span {
+    let span = info_span!(
+        "input",
+        request_type = input.request_type.as_str(),
+        epoch_index = field::Empty,
+        input_index = field::Empty,
+        correlation_id = field::Empty,
+    );
+    if let Some(metadata) = &input.data.metadata {
+        span.record("epoch_index", metadata.epoch_index);
+        span.record("input_index", metadata.input_index);
+        span.record(
+            "correlation_id",
+            correlation_id(metadata.epoch_index, metadata.input_index),
+        );
+    }
+    span
+}
+
+/**
+ * Run every DApp call inside a span keyed by the input the DApp is handling
+ */
+pub async fn trace_dapp_calls(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // consumers answer under /consumers/{name}/, the default consumer everywhere else
+    let consumer = match req.path().strip_prefix("/consumers/") {
+        Some(path) => path.split('/').next().unwrap_or_default().to_string(),
+        None => DEFAULT_CONSUMER.to_string(),
+    };
+    let span = info_span!(
+        "dapp_call",
+        method = %req.method(),
+        path = req.path(),
+        consumer = consumer.as_str(),
+        epoch_index = field::Empty,
+        input_index = field::Empty,
+        correlation_id = field::Empty,
+    );
+
+    let ctx = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|ctx| ctx.for_consumer(&consumer));
+    if let Some(ctx) = ctx {
+        if let Some(input) = ctx.current_input().await {
+            span.record("epoch_index", input.epoch_index);
+            span.record("input_index", input.index);
+            span.record(
+                "correlation_id",
+                correlation_id(input.epoch_index, input.index),
+            );
+        }
+    }
+
+    next.call(req).instrument(span).await
+}
@@ -0,0 +1,111 @@
+use std::error::Error;
+
+use tracing::info;
+
+use crate::models::structs::{PayloadWithTimelock, TimelockCiphertext};
+
+/**
+ * Check if the request carries a timelock ciphertext
+ * Example of a timelock request, the ciphertext is the age file produced by tlock (armored or 0x hex)
+ *
+ * {"timelock":{"id":"bid-1","ciphertext":"-----BEGIN AGE ENCRYPTED FILE-----\n...\n-----END AGE ENCRYPTED FILE-----\n"}}
+ */
+pub fn get_timelock(payload: &str) -> Result<TimelockCiphertext, Box<dyn Error>> {
+    let payload = payload.trim_start_matches("0x");
+    let payload = hex::decode(payload)?;
+    let payload = std::str::from_utf8(&payload)?;
+
+    let payload = serde_json::from_str::<PayloadWithTimelock>(payload)?;
+
+    let ciphertext = match payload.timelock.ciphertext.strip_prefix("0x") {
+        Some(binary) => hex::decode(binary)?,
+        None => payload.timelock.ciphertext.into_bytes(),
+    };
+
+    let header = tlock_age::decrypt_header(ciphertext.as_slice())?;
+    info!(
+        "Timelock {} sealed until round {}",
+        payload.timelock.id,
+        header.round()
+    );
+
+    Ok(TimelockCiphertext {
+        id: payload.timelock.id,
+        round: header.round(),
+        ciphertext,
+        plaintext: None,
+    })
+}
+
+/**
+ * Open an age-tlock file with the signature of its round.
+ * The signature must have been verified against the drand public key before.
+ */
+pub fn decrypt_timelock(ciphertext: &[u8], signature: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let header = tlock_age::decrypt_header(ciphertext)?;
+
+    let mut plaintext = vec![];
+    tlock_age::decrypt(&mut plaintext, ciphertext, &header.hash(), signature)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    pub(crate) const QUICKNET_ROUND: u64 = 1000;
+    pub(crate) const QUICKNET_SIGNATURE: &str = "b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39";
+
+    /**
+     * "sealed bid: 42" encrypted to quicknet round 1000 by tlock-rs (same format as `tle -c quicknet -r 1000 -a`)
+     */
+    pub(crate) const QUICKNET_CIPHERTEXT: &str = "-----BEGIN AGE ENCRYPTED FILE-----\nYWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+IHRsb2NrIDEwMDAgNTJkYjliYTcwZTBj\nYzBmNmVhZjc4MDNkZDA3NDQ3YTFmNTQ3NzczNWZkM2Y2NjE3OTJiYTk0NjAwYzg0\nZTk3MQpvMng1YVJKY2NnL3VkQk96RUpzTGhPYUltcDBwWjdZWk55bXZUb3FzMU5V\nOFlrM2RVOTVpUDBtSFQrWThWS1l5CkNRZnBObTNFVHBpeHFERHZPSzE3WGZRMFkw\nMFJyZjZsbjZ1WWZ4RzFWbDYwWUtyQ093QWNseG1lWmFqd29udDAKZ0VTWENORDda\nNTgwMU53RFYyRUxZUmlrTjdqcDVETWRsaS9EampqbUpvRQotPiA2LzR+UlB0SS1n\ncmVhc2UgckcgTDFJfFU5KEcgUUFYdHM6CkVOcUhLZmdSTGJZdkNNUnBsN1RmNklq\nRVVKMTdvMVJzSlR3Ci0tLSBQSjQ5VnlLd3VsYndkalZ1cEJEWnloQUhOYnVQaXQr\nMHVXa2dvakFyU2ZVCkiaorzue1z4z3S13BzlX1yP2r4I3dwtiPxVsknYWaXDi6Fx\n1J3PhZLWBCf7Nsg=\n-----END AGE ENCRYPTED FILE-----\n";
+
+    /**
+     * The same file without the ascii armor
+     */
+    const QUICKNET_CIPHERTEXT_HEX: &str = "6167652d656e6372797074696f6e2e6f72672f76310a2d3e20746c6f636b203130303020353264623962613730653063633066366561663738303364643037343437613166353437373733356664336636363137393262613934363030633834653937310a6f32783561524a6363672f7564424f7a454a734c684f61496d7030705a37595a4e796d76546f7173314e5538596b33645539356950306d48542b5938564b59790a435166704e6d334554706978714444764f4b313758665130593030527266366c6e36755966784731566c3630594b72434f7741636c786d655a616a776f6e74300a67455358434e44375a353830314e77445632454c5952696b4e376a7035444d646c692f446a6a6a6d4a6f450a2d3e20362f347e525074492d677265617365207247204c31497c553928472051415874733a0a454e71484b6667524c625976434d52706c37546636496a45554a31376f3152734a54770a2d2d2d20504a343956794b77756c6277646a56757042445a796841484e62755069742b3075576b676f6a41725366550a489aa2bcee7b5cf8cf74b5dc1ce55f5c8fdabe08dddc2d88fc55b249d859a5c38ba171d49dcf8592d60427fb36c8";
+
+    #[test]
+    fn test_decrypt_timelock() {
+        let signature = hex::decode(QUICKNET_SIGNATURE).unwrap();
+        let plaintext = decrypt_timelock(QUICKNET_CIPHERTEXT.as_bytes(), &signature).unwrap();
+        assert_eq!(plaintext, b"sealed bid: 42");
+
+        let mut wrong = signature.clone();
+        wrong[47] ^= 1;
+        assert!(decrypt_timelock(QUICKNET_CIPHERTEXT.as_bytes(), &wrong).is_err());
+        assert!(decrypt_timelock(QUICKNET_CIPHERTEXT.as_bytes(), &signature[..32]).is_err());
+    }
+
+    #[test]
+    fn test_get_timelock_from_payload() {
+        let ciphertext = QUICKNET_CIPHERTEXT.as_bytes();
+        let payload = serde_json::json!({
+            "timelock": { "id": "bid-1", "ciphertext": QUICKNET_CIPHERTEXT }
+        });
+        let payload = hex::encode(payload.to_string());
+
+        let timelock = get_timelock(&payload).unwrap();
+        assert_eq!(timelock.id, "bid-1");
+        assert_eq!(timelock.round, QUICKNET_ROUND);
+        assert_eq!(timelock.ciphertext, ciphertext);
+        assert!(timelock.plaintext.is_none());
+
+        let binary = hex::decode(QUICKNET_CIPHERTEXT_HEX).unwrap();
+        let payload = serde_json::json!({
+            "timelock": { "id": "bid-2", "ciphertext": format!("0x{}", hex::encode(&binary)) }
+        });
+        let timelock = get_timelock(&hex::encode(payload.to_string())).unwrap();
+        assert_eq!(timelock.round, QUICKNET_ROUND);
+        assert_eq!(timelock.ciphertext, binary);
+
+        let signature = hex::decode(QUICKNET_SIGNATURE).unwrap();
+        assert_eq!(
+            decrypt_timelock(&binary, &signature).unwrap(),
+            b"sealed bid: 42"
+        );
+
+        assert!(get_timelock(&hex::encode(r#"{"beacon":{}}"#)).is_err());
+    }
+}
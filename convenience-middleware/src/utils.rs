@@ -1,12 +1,18 @@
 pub mod util {
-    use std::{error::Error, path::Path};
+    use std::{
+        error::Error,
+        path::{Path, PathBuf},
+    };
 
     use dotenvy::var;
-    use log::info;
     use serde_json::Value;
-    use tokio::fs::read_to_string;
+    use tokio::{
+        fs::read_to_string,
+        signal::unix::{signal, SignalKind},
+    };
+    use tracing::info;
 
-    use crate::models::structs::DrandEnv;
+    use crate::models::structs::{DrandEnv, Snapshot};
 
     pub fn generate_payload_hex<T>(json: T) -> Result<String, Box<dyn Error>>
     where
@@ -65,6 +71,65 @@ pub mod util {
         }
     }
 
+    /**
+     * The PUT /update_drand_config endpoint is off unless ENABLE_DRAND_CONFIG_ENDPOINT=true,
+     * the owner should send a drand_config_update input instead
+     */
+    pub fn is_drand_config_endpoint_enabled() -> bool {
+        var("ENABLE_DRAND_CONFIG_ENDPOINT")
+            .map(|value| value.eq_ignore_ascii_case("true"))
+            .unwrap_or(false)
+    }
+
+    /**
+     * Apply a new drand config and persist it to drand.config.json
+     */
+    pub async fn store_drand_config(drand: DrandEnv) -> Result<(), Box<dyn Error>> {
+        load_env_from_memory(drand).await;
+        write_env_to_json().await
+    }
+
+    /**
+     * SNAPSHOT_FILE env, middleware.snapshot.json when unset
+     */
+    fn snapshot_path() -> PathBuf {
+        PathBuf::from(var("SNAPSHOT_FILE").unwrap_or("middleware.snapshot.json".to_string()))
+    }
+
+    pub async fn write_snapshot(snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+        let path = snapshot_path();
+        let content = serde_json::to_string_pretty(snapshot)?;
+        tokio::fs::write(&path, content).await?;
+        info!("Snapshot written to {}", path.display());
+        Ok(())
+    }
+
+    /**
+     * Read the snapshot and remove it, so a crash after the restore doesn't replay it
+     */
+    pub async fn take_snapshot() -> Result<Snapshot, Box<dyn Error>> {
+        let path = snapshot_path();
+        if !path.exists() {
+            return Err("File not found".into());
+        }
+        let content = read_to_string(&path).await?;
+        let snapshot = serde_json::from_str::<Snapshot>(&content)?;
+        tokio::fs::remove_file(&path).await?;
+        Ok(snapshot)
+    }
+
+    /**
+     * Resolve on SIGTERM or SIGINT
+     */
+    pub async fn shutdown_signal() -> Result<(), Box<dyn Error>> {
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+        Ok(())
+    }
+
     pub async fn load_env_from_json() -> Result<(), Box<dyn Error>> {
         info!("Loading env from json");
 
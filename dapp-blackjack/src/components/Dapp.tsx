@@ -308,11 +308,6 @@ export class Dapp extends React.Component<{}, DappState> {
                     {/* http://localhost:1234/?debug */}
                     {this.DEBUG && (
                         <div className="col-12">
-                            <button
-                                onClick={() => {
-                                    this._updateDrandPublicKey()
-                                }}
-                            >Update Drand Public Key</button>
                             <pre>
                                 {JSON.stringify(this.state.hands || {}, null, 4)}
                             </pre>
@@ -364,16 +359,6 @@ export class Dapp extends React.Component<{}, DappState> {
         }
     }
 
-    private async _updateDrandPublicKey() {
-        this.checkSigner(this._signer);
-        this.checkProvider(this._provider);
-        await Cartesi.sendInput({
-            action: "update_drand",
-            genesis_time: 1692803367,
-            public_key: "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a",
-        }, this._signer, this._provider)
-    }
-
     private async _chooseStand() {
         const game_id = this.state.gameIdSelected;
         this.checkGameIdSelected(game_id);
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+use dapp_contract_blackjack::rollups::rollup::{get_from_payload_action, get_payload_from_root};
+use dapp_contract_blackjack::util::json::{decode_payload, get_address_metadata_from_root};
+
+fuzz_target!(|data: &[u8]| {
+    // the hex payload of an input
+    if let Ok(payload) = decode_payload::<Value>(&format!("0x{}", hex::encode(data))) {
+        let _ = get_from_payload_action(&payload);
+    }
+    if let Ok(payload) = std::str::from_utf8(data) {
+        let _ = decode_payload::<Value>(payload);
+    }
+
+    // a whole request from the middleware
+    if let Ok(root) = serde_json::from_slice::<Value>(data) {
+        let _ = get_payload_from_root::<Value>(&root);
+        let _ = get_address_metadata_from_root(&root);
+    }
+});
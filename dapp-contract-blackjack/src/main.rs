@@ -1,16 +1,11 @@
 use std::sync::Arc;
 
-#[path = "./mod.rs"]
-mod imports;
-
-use imports::*;
-
+use dapp_contract_blackjack::models::game::prelude::Manager;
+use dapp_contract_blackjack::rollups::rollup::rollup;
+use dapp_contract_blackjack::util::logger;
 use dotenvy::{dotenv, var};
-use log::{error, info};
-use rollups::rollup::rollup;
 use tokio::sync::Mutex;
-
-use crate::models::game::prelude::Manager;
+use tracing::{error, info};
 
 // Read from rollup and send to handle
 async fn start_rollup(manager: Arc<Mutex<Manager>>) {
@@ -26,7 +21,7 @@ async fn main() {
     dotenv().unwrap();
     var("MIDDLEWARE_HTTP_SERVER_URL").expect("Middleware http server must be set");
 
-    env_logger::builder().format_timestamp(None).init();
+    logger::init();
 
     const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
     info!("BlackJack v{}", VERSION.unwrap_or("unknown"));
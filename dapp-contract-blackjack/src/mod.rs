@@ -1,6 +0,0 @@
-#[path = "models/mod.rs"]
-pub mod models;
-#[path = "rollups.rs"]
-pub mod rollups;
-#[path = "util.rs"]
-pub mod util;
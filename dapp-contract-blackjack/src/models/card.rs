@@ -1,7 +1,7 @@
 pub mod prelude {
     use std::fmt::Display;
 
-    use log::error;
+    use tracing::error;
 
     #[derive(Clone)]
     pub enum Suit {
@@ -2,20 +2,38 @@ pub mod prelude {
     use crate::{
         models::{
             card::prelude::Deck,
-            player::prelude::{Player, PlayerHand},
+            player::prelude::{Credit, Player, PlayerHand},
         },
         util::{json::generate_report, random::generate_id},
     };
-    use log::info;
+    use dotenvy::var;
     use serde_json::{json, Value};
-    use std::{collections::HashMap, sync::Arc};
+    use std::{collections::HashMap, str::FromStr, sync::Arc};
     use tokio::sync::Mutex;
+    use tracing::{error, info, warn};
+
+    /**
+     * The dealer's id, never a valid base58 address because it has an "l".
+     */
+    pub const DEALER_ID: &str = "dealer";
+
+    pub const CHIPS: &str = "CHIPS";
+
+    /**
+     * Hands a player may have after splitting and re-splitting, unless MAX_SPLIT_HANDS says otherwise.
+     */
+    pub const MAX_SPLIT_HANDS: usize = 4;
 
     #[derive(Default)]
     pub struct Manager {
         pub games: Vec<Game>, // games to be started. A player can join this game
         pub players: HashMap<String, Arc<Player>>,
         pub tables: HashMap<String, Table>, // games running
+        balances: HashMap<String, Credit>,
+        // What was deposited below the price of a chip, by player and asset
+        deposit_change: HashMap<(String, String), u128>,
+        // Chips bought and not yet withdrawn, by player and asset, the only chips paid out
+        withdrawable: HashMap<(String, String), u32>,
         scoreboards: Vec<Scoreboard>,
         pub games_report_cache: Option<Value>,
     }
@@ -36,6 +54,9 @@ pub mod prelude {
                 games,
                 tables: HashMap::with_capacity(game_size),
                 players: HashMap::new(),
+                balances: HashMap::new(),
+                deposit_change: HashMap::new(),
+                withdrawable: HashMap::new(),
                 scoreboards: Vec::new(),
                 games_report_cache: Some(report),
             }
@@ -48,6 +69,8 @@ pub mod prelude {
                     json!({
                         "id": game.get_id(),
                         "players": game.players.len(),
+                        "min_bet": game.limits.min,
+                        "max_bet": game.limits.max,
                     })
                 })
                 .collect::<Vec<_>>();
@@ -85,6 +108,240 @@ pub mod prelude {
             self.players.get(id).ok_or("Player not found.")
         }
 
+        pub fn get_balance(&self, player_id: &str) -> u32 {
+            self.balances
+                .get(player_id)
+                .map_or(0, |balance| balance.amount)
+        }
+
+        pub fn credit_balance(
+            &mut self,
+            player_id: &str,
+            amount: u32,
+        ) -> Result<u32, &'static str> {
+            let balance = self
+                .balances
+                .entry(player_id.to_string())
+                .or_insert_with(|| Credit {
+                    amount: 0,
+                    symbol: CHIPS.to_string(),
+                });
+
+            balance.amount = balance
+                .amount
+                .checked_add(amount)
+                .ok_or("Balance overflow.")?;
+            Ok(balance.amount)
+        }
+
+        pub fn debit_balance(&mut self, player_id: &str, amount: u32) -> Result<u32, &'static str> {
+            let balance = self
+                .balances
+                .get_mut(player_id)
+                .ok_or("Insufficient balance.")?;
+
+            balance.amount = balance
+                .amount
+                .checked_sub(amount)
+                .ok_or("Insufficient balance.")?;
+            Ok(balance.amount)
+        }
+
+        /**
+         * Buy chips with a deposit of `amount` units of `asset`, the change is kept for the next deposit.
+         * Returns the chips credited.
+         */
+        pub fn deposit(
+            &mut self,
+            player_id: &str,
+            asset: &str,
+            amount: u128,
+            price: u128,
+        ) -> Result<u32, &'static str> {
+            if price == 0 {
+                return Err("Invalid chip price");
+            }
+
+            let key = (player_id.to_string(), asset.to_string());
+            let change = self.deposit_change.get(&key).copied().unwrap_or(0);
+            let total = amount.checked_add(change).ok_or("Deposit too large.")?;
+
+            let chips = u32::try_from(total / price).or(Err("Deposit too large."))?;
+            self.credit_balance(player_id, chips)?;
+
+            match total % price {
+                0 => self.deposit_change.remove(&key),
+                change => self.deposit_change.insert(key.clone(), change),
+            };
+
+            let withdrawable = self.withdrawable.entry(key).or_insert(0);
+            *withdrawable = withdrawable.saturating_add(chips);
+
+            Ok(chips)
+        }
+
+        /**
+         * Chips of the player that can still be paid out in `asset`.
+         */
+        pub fn get_withdrawable(&self, player_id: &str, asset: &str) -> u32 {
+            self.withdrawable
+                .get(&(player_id.to_string(), asset.to_string()))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /**
+         * Debit chips to be paid out in `asset`, up to the chips the player bought with it.
+         * Initial chips and winnings are never paid out. Returns the balance left.
+         */
+        pub fn withdraw(
+            &mut self,
+            player_id: &str,
+            asset: &str,
+            chips: u32,
+        ) -> Result<u32, &'static str> {
+            if chips > self.get_withdrawable(player_id, asset) {
+                return Err("Withdrawal exceeds the chips deposited.");
+            }
+
+            let balance = self.debit_balance(player_id, chips)?;
+            let key = (player_id.to_string(), asset.to_string());
+            match self.get_withdrawable(player_id, asset) - chips {
+                0 => self.withdrawable.remove(&key),
+                withdrawable => self.withdrawable.insert(key, withdrawable),
+            };
+
+            Ok(balance)
+        }
+
+        /**
+         * Give back the chips of a withdrawal that could not be paid out.
+         */
+        pub fn cancel_withdraw(
+            &mut self,
+            player_id: &str,
+            asset: &str,
+            chips: u32,
+        ) -> Result<u32, &'static str> {
+            let balance = self.credit_balance(player_id, chips)?;
+            let withdrawable = self
+                .withdrawable
+                .entry((player_id.to_string(), asset.to_string()))
+                .or_insert(0);
+            *withdrawable = withdrawable.saturating_add(chips);
+
+            Ok(balance)
+        }
+
+        /**
+         * Take the bet from the player's balance, a new bet replaces and refunds the previous one.
+         */
+        pub fn place_bet(
+            &mut self,
+            game_id: &str,
+            player_id: &str,
+            amount: u32,
+        ) -> Result<(), &'static str> {
+            let game = self.get_game_by_id(game_id)?;
+
+            if !game.has_player(player_id) {
+                return Err("Player didnt join the game.");
+            }
+
+            game.limits.check(amount)?;
+
+            let previous = game.bets.get(player_id).copied().unwrap_or(0);
+
+            if self.get_balance(player_id).saturating_add(previous) < amount {
+                return Err("Insufficient balance.");
+            }
+
+            self.credit_balance(player_id, previous)?;
+            self.debit_balance(player_id, amount)?;
+
+            let game = self.get_game_by_id(game_id)?;
+            game.bets.insert(player_id.to_string(), amount);
+            Ok(())
+        }
+
+        /**
+         * Whether the player may double down now, checked before any randomness is spent.
+         * Returns the bet to take again from the balance.
+         */
+        pub fn can_double_down(
+            &self,
+            table_id: &str,
+            player_id: &str,
+        ) -> Result<u32, &'static str> {
+            let table = self.get_table_ref(table_id)?;
+            let hand = table.get_player_by_id(player_id)?;
+            hand.can_double_down()?;
+            let bet = hand.get_bet();
+            table.get_player_in_round(player_id)?;
+
+            if self.get_balance(player_id) < bet {
+                return Err("Insufficient balance.");
+            }
+            Ok(bet)
+        }
+
+        /**
+         * Double the player's bet with chips from the balance, the player draws one card and stands.
+         */
+        pub async fn double_down(
+            &mut self,
+            table_id: &str,
+            player_id: &str,
+            timestamp: u64,
+            seed: &str,
+        ) -> Result<(), &'static str> {
+            let bet = self.can_double_down(table_id, player_id)?;
+            let table = self.get_table_mut(table_id)?;
+            table.double_down_player(player_id, timestamp, seed).await?;
+
+            if bet > 0 {
+                self.debit_balance(player_id, bet)?;
+            }
+            Ok(())
+        }
+
+        /**
+         * Whether the player may split now, checked before any randomness is spent.
+         * Returns the bet the new hand takes from the balance.
+         */
+        pub fn can_split(&self, table_id: &str, player_id: &str) -> Result<u32, &'static str> {
+            let table = self.get_table_ref(table_id)?;
+            let bet = table.can_split_player(player_id)?.get_bet();
+
+            if self.get_balance(player_id) < bet {
+                return Err("Insufficient balance.");
+            }
+            Ok(bet)
+        }
+
+        /**
+         * Split the player's hand, the new hand takes a bet equal to the original one.
+         */
+        pub async fn split(
+            &mut self,
+            table_id: &str,
+            player_id: &str,
+            timestamp: u64,
+            seed: &str,
+            split_seed: &str,
+        ) -> Result<(), &'static str> {
+            let bet = self.can_split(table_id, player_id)?;
+            let table = self.get_table_mut(table_id)?;
+            table
+                .split_player(player_id, timestamp, seed, split_seed)
+                .await?;
+
+            if bet > 0 {
+                self.debit_balance(player_id, bet)?;
+            }
+            Ok(())
+        }
+
         pub fn first_game_available(&mut self) -> Result<&mut Game, &'static str> {
             self.games.first_mut().ok_or("No games available.")
         }
@@ -129,24 +386,45 @@ pub mod prelude {
         }
 
         pub fn generate_scoreboard_sync(&mut self, table: &Table) {
-            let players = table.game.players.to_vec();
-
-            let winner = table.get_winner_sync();
-            let scoreboard_id = table.id.clone();
-            let hands = table.generate_hands();
-            let scoreboard =
-                Scoreboard::new(&scoreboard_id, table.game.get_id(), players, winner, hands);
-            self.scoreboards.push(scoreboard);
+            self.push_scoreboard(table, table.settle());
         }
 
         pub async fn generate_scoreboard(&mut self, table: &Table) {
+            // Safe for check hands, anyone cant pick a card.
+            let _deck = table.deck.lock().await;
+            self.push_scoreboard(table, table.settle());
+        }
+
+        /**
+         * Pay every player's settlement into the balances and keep the result.
+         */
+        fn push_scoreboard(&mut self, table: &Table, settlements: Vec<Settlement>) {
             let players = table.game.players.to_vec();
 
-            let winner = table.get_winner().await;
+            let results = settlements
+                .into_iter()
+                .map(|settlement| {
+                    let player_id = settlement.player.get_id();
+                    if settlement.is_paid {
+                        return (settlement, self.get_balance(&player_id));
+                    }
+                    let balance = self
+                        .credit_balance(&player_id, settlement.payout)
+                        .unwrap_or_else(|e| {
+                            error!(
+                                "Could not pay {} to {}: {}",
+                                settlement.payout, player_id, e
+                            );
+                            self.get_balance(&player_id)
+                        });
+                    (settlement, balance)
+                })
+                .collect::<Vec<_>>();
+
             let scoreboard_id = table.id.clone();
-            let hands = table.generate_hands();
+            let hands = table.generate_final_hands();
             let scoreboard =
-                Scoreboard::new(&scoreboard_id, table.game.get_id(), players, winner, hands);
+                Scoreboard::new(&scoreboard_id, table.game.get_id(), players, results, hands);
             self.scoreboards.push(scoreboard);
         }
 
@@ -155,9 +433,12 @@ pub mod prelude {
          */
         pub async fn reallocate_table_to_game(&mut self, table: Table) {
             self.generate_scoreboard(&table).await;
+            self.return_to_lobby(table.game);
+        }
 
-            let mut game = table.game;
+        fn return_to_lobby(&mut self, mut game: Game) {
             game.players.clear();
+            game.bets.clear();
             self.add_game(game);
         }
 
@@ -174,6 +455,14 @@ pub mod prelude {
             // .or_else(|| self.tables.values().find(|table| table.game.get_id() == id))
         }
 
+        fn get_table_ref(&self, id: &str) -> Result<&Table, &'static str> {
+            if self.tables.is_empty() {
+                return Err("No tables running.");
+            }
+
+            self.tables.get(id).ok_or("Table not found or not started.")
+        }
+
         pub fn get_table_mut(&mut self, id: &str) -> Result<&mut Table, &'static str> {
             if self.tables.is_empty() {
                 return Err("No tables running.");
@@ -197,6 +486,57 @@ pub mod prelude {
             Ok(())
         }
 
+        /**
+         * Pay the players' naturals as soon as the dealer peeked without one.
+         */
+        pub fn settle_naturals(&mut self, table_id: &str) -> Result<(), &'static str> {
+            let table = self.get_table_mut(table_id)?;
+
+            for settlement in table.settle_naturals() {
+                let player_id = settlement.player.get_id();
+                info!("Natural of {} pays {}", player_id, settlement.payout);
+                self.credit_balance(&player_id, settlement.payout)?;
+            }
+            Ok(())
+        }
+
+        /**
+         * Stop a table before its round is over, the open bets are refunded instead of settled.
+         */
+        pub async fn cancel_table(&mut self, table_id: &str) -> Result<(), &'static str> {
+            info!("Cancelling game table_id {}", table_id);
+
+            let table = self
+                .tables
+                .remove(table_id)
+                .ok_or("Table not found or not started.")?;
+
+            {
+                // Safe for check hands, anyone cant pick a card.
+                let _deck = table.deck.lock().await;
+                self.push_scoreboard(&table, table.refund());
+            }
+            self.return_to_lobby(table.game);
+
+            Ok(())
+        }
+
+        /**
+         * Close a game that never started, every bet goes back to its player.
+         */
+        pub fn cancel_game(&mut self, game_id: &str) -> Result<(), &'static str> {
+            info!("Cancelling game game_id {}", game_id);
+
+            let game = self.get_game_by_id(game_id)?;
+            let bets = std::mem::take(&mut game.bets);
+            game.players.clear();
+
+            for (player_id, bet) in bets {
+                self.credit_balance(&player_id, bet)?;
+            }
+            Ok(())
+        }
+
         pub fn player_join(
             &mut self,
             game_id: &str,
@@ -229,7 +569,7 @@ pub mod prelude {
         id: String,
         game_id: String,
         players: Vec<Arc<Player>>,
-        winner: Option<Arc<Player>>,
+        results: Vec<(Settlement, u32)>,
         hands: Value,
     }
     impl Scoreboard {
@@ -237,26 +577,37 @@ pub mod prelude {
             id: &str,
             game_id: &str,
             players: Vec<Arc<Player>>,
-            winner: Option<Arc<Player>>,
+            results: Vec<(Settlement, u32)>,
             hands: Value,
         ) -> Self {
-            info!(
-                "Scoreboard {}; game_id {}; winner {:?}",
-                id, game_id, winner
-            );
+            info!("Scoreboard {}; game_id {}", id, game_id);
             Scoreboard {
                 id: id.to_string(),
                 game_id: game_id.to_string(),
                 players,
-                winner,
+                results,
                 hands,
             }
         }
 
+        /**
+         * Each settlement with the player's balance after the payout.
+         */
+        pub fn get_results(&self) -> &[(Settlement, u32)] {
+            &self.results
+        }
+
+        pub fn get_ranking(&self) -> Vec<(usize, &Settlement)> {
+            ranking(self.results.iter().map(|(settlement, _)| settlement))
+        }
+
+        pub fn get_winner(&self) -> Option<Arc<Player>> {
+            winner(&self.get_ranking())
+        }
+
         pub fn to_json(&self) -> Value {
             let winner = self
-                .winner
-                .as_ref()
+                .get_winner()
                 .map_or("DRAW".to_string(), |player| player.name.clone());
 
             let value = json!({
@@ -264,6 +615,29 @@ pub mod prelude {
                 "game_id": self.game_id,
                 "players": self.players.iter().map(|player| player.name.clone()).collect::<Vec<_>>(),
                 "winner": winner,
+                "surrendered": self
+                    .results
+                    .iter()
+                    .filter(|(settlement, _)| settlement.outcome == Outcome::Surrender)
+                    .map(|(settlement, _)| settlement.player.name.clone())
+                    .collect::<Vec<_>>(),
+                "ranking": self.get_ranking().iter().map(|(position, settlement)| json!({
+                    "position": position,
+                    "name": settlement.player.name,
+                    "result": settlement.outcome.as_str(),
+                    "points": settlement.points,
+                    "is_blackjack": settlement.is_blackjack,
+                })).collect::<Vec<_>>(),
+                "results": self.results.iter().map(|(settlement, balance)| json!({
+                    "name": settlement.player.name,
+                    "result": settlement.outcome.as_str(),
+                    "points": settlement.points,
+                    "is_blackjack": settlement.is_blackjack,
+                    "bet": settlement.bet,
+                    "payout": settlement.payout,
+                    "balance_change": settlement.payout as i64 - settlement.bet as i64,
+                    "balance": balance,
+                })).collect::<Vec<_>>(),
             });
 
             json!({
@@ -274,22 +648,208 @@ pub mod prelude {
         }
     }
 
+    /**
+     * Every player ranked by their best hand, tied players share a position.
+     * A win ranks above a push, above a loss, above a busted or surrendered hand, then by points and a natural above any other 21.
+     */
+    fn ranking<'a>(
+        settlements: impl Iterator<Item = &'a Settlement>,
+    ) -> Vec<(usize, &'a Settlement)> {
+        let mut best: Vec<&Settlement> = vec![];
+
+        for settlement in settlements {
+            let player_id = settlement.player.get_id();
+            match best
+                .iter_mut()
+                .find(|best| best.player.get_id() == player_id)
+            {
+                Some(best) if settlement.rank() > best.rank() => *best = settlement,
+                Some(_) => {}
+                None => best.push(settlement),
+            }
+        }
+
+        best.sort_by_key(|settlement| std::cmp::Reverse(settlement.rank()));
+
+        best.iter()
+            .map(|settlement| {
+                let ahead = best
+                    .iter()
+                    .filter(|other| other.rank() > settlement.rank())
+                    .count();
+                (ahead + 1, *settlement)
+            })
+            .collect()
+    }
+
+    /**
+     * The only player ranked first, if that hand beat the dealer.
+     */
+    fn winner(ranking: &[(usize, &Settlement)]) -> Option<Arc<Player>> {
+        match ranking {
+            [(1, first), (2.., _), ..] | [(1, first)] if first.outcome == Outcome::Win => {
+                Some(first.player.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Whether the dealer draws on a soft 17, chosen by the DEALER_SOFT_17 env (stand or hit).
+     */
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum Soft17Rule {
+        #[default]
+        Stand,
+        Hit,
+    }
+
+    impl FromStr for Soft17Rule {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.to_lowercase().as_str() {
+                "stand" => Ok(Soft17Rule::Stand),
+                "hit" => Ok(Soft17Rule::Hit),
+                _ => Err(format!("Unknown soft 17 rule {}", value)),
+            }
+        }
+    }
+
+    impl Soft17Rule {
+        pub fn from_env() -> Soft17Rule {
+            match var("DEALER_SOFT_17") {
+                Ok(value) => value.parse().unwrap_or_else(|e| {
+                    warn!("{}, dealer stands on soft 17", e);
+                    Soft17Rule::Stand
+                }),
+                Err(_) => Soft17Rule::default(),
+            }
+        }
+    }
+
+    /**
+     * Result of a player's hand settled against the dealer's.
+     */
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Outcome {
+        Win,
+        Lose,
+        Push,
+        Bust,
+        Surrender,
+        Refund,
+    }
+
+    impl Outcome {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Outcome::Win => "win",
+                Outcome::Lose => "lose",
+                Outcome::Push => "push",
+                Outcome::Bust => "bust",
+                Outcome::Surrender => "surrender",
+                Outcome::Refund => "refund",
+            }
+        }
+    }
+
+    /**
+     * A player's outcome and what the bet pays back, the bet itself included.
+     */
+    pub struct Settlement {
+        pub player: Arc<Player>,
+        pub outcome: Outcome,
+        pub points: u8,
+        pub is_blackjack: bool,
+        pub bet: u32,
+        pub payout: u32,
+        // The payout already went to the balance at the dealer peek
+        pub is_paid: bool,
+    }
+
+    impl Settlement {
+        /**
+         * Sort key of the hand, its outcome against the dealer, its points and whether it's a natural.
+         */
+        fn rank(&self) -> (u8, u8, bool) {
+            let outcome = match self.outcome {
+                Outcome::Bust | Outcome::Surrender | Outcome::Refund => return (0, 0, false),
+                Outcome::Lose => 1,
+                Outcome::Push => 2,
+                Outcome::Win => 3,
+            };
+            (outcome, self.points, self.is_blackjack)
+        }
+    }
+
+    /**
+     * Bet limits of a game, from the MIN_BET and MAX_BET env (1 and 100 by default).
+     */
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct BetLimits {
+        pub min: u32,
+        pub max: u32,
+    }
+
+    impl Default for BetLimits {
+        fn default() -> Self {
+            BetLimits { min: 1, max: 100 }
+        }
+    }
+
+    impl BetLimits {
+        pub fn new(min: u32, max: u32) -> Result<Self, &'static str> {
+            if min == 0 || min > max {
+                return Err("Invalid bet limits.");
+            }
+            Ok(BetLimits { min, max })
+        }
+
+        pub fn from_env() -> BetLimits {
+            let default = BetLimits::default();
+            let parse = |key: &str, default: u32| {
+                var(key).map_or(default, |value| {
+                    value.parse().unwrap_or_else(|_| {
+                        warn!("Invalid env {}, using {}", key, default);
+                        default
+                    })
+                })
+            };
+
+            let min = parse("MIN_BET", default.min);
+            let max = parse("MAX_BET", default.max);
+
+            BetLimits::new(min, max).unwrap_or_else(|e| {
+                warn!("{} min {} max {}, using defaults", e, min, max);
+                default
+            })
+        }
+
+        pub fn check(&self, amount: u32) -> Result<(), &'static str> {
+            if amount < self.min {
+                return Err("Bet below the game minimum.");
+            }
+            if amount > self.max {
+                return Err("Bet above the game maximum.");
+            }
+            Ok(())
+        }
+    }
+
     /**
      * This is where the game is initialized.
      */
     pub struct Game {
         id: String,
         pub players: Vec<Arc<Player>>,
-        manager: Option<Arc<Mutex<Manager>>>,
+        pub limits: BetLimits,
+        bets: HashMap<String, u32>,
     }
 
     impl Default for Game {
         fn default() -> Self {
-            Game {
-                id: generate_id(),
-                players: Vec::new(),
-                manager: None,
-            }
+            Game::with_id(generate_id())
         }
     }
 
@@ -298,10 +858,21 @@ pub mod prelude {
             Game {
                 id,
                 players: Vec::new(),
-                manager: None,
+                limits: BetLimits::from_env(),
+                bets: HashMap::new(),
             }
         }
 
+        pub fn get_bet(&self, player_id: &str) -> Option<u32> {
+            self.bets.get(player_id).copied()
+        }
+
+        pub fn all_players_bet(&self) -> bool {
+            self.players
+                .iter()
+                .all(|player| self.bets.contains_key(&player.get_id()))
+        }
+
         pub fn get_id(&self) -> &str {
             &self.id
         }
@@ -324,65 +895,65 @@ pub mod prelude {
         }
     }
 
+    fn max_split_hands_from_env() -> usize {
+        match var("MAX_SPLIT_HANDS").map(|value| value.parse::<usize>()) {
+            Ok(Ok(max)) if max >= 1 => max,
+            Ok(_) => {
+                warn!("Invalid env MAX_SPLIT_HANDS, using {}", MAX_SPLIT_HANDS);
+                MAX_SPLIT_HANDS
+            }
+            Err(_) => MAX_SPLIT_HANDS,
+        }
+    }
+
     /**
      * The table is where the game is played.
      */
     pub struct Table {
         pub deck: Arc<Mutex<Deck>>,
         players_with_hand: Vec<PlayerHand>,
+        dealer: PlayerHand,
+        soft_17: Soft17Rule,
+        max_split_hands: usize,
         game: Game,
         round: u8,
         id: String,
+        // Address of the player who started the table, allowed to stop it
+        owner: Option<String>,
         // Cache for hand
         report: Option<Value>,
     }
 
-    // TODO
-    // impl Drop for Table {
-    //     fn drop(&mut self) {
-    //         let manager = self.game.manager.clone();
-
-    //         if let Some(manager) = manager {
-    //             let locker = manager.try_lock().map(|mut manager| {
-    //                 manager.generate_scoreboard(self);
-
-    //                 let mut game = Game::default();
-    //                 game.manager = self.game.manager.clone();
-    //                 manager.games.push(game);
-    //             });
-
-    //             if let Err(err) = locker {
-    //                 error!("{}", err)
-    //             }
-    //         } else {
-    //             error!("Table dont have reference")
-    //         }
-    //     }
-    // }
-
     impl Table {
         fn new(game: Game, nth_decks: usize, last_timestamp: u64) -> Result<Self, &'static str> {
             // let bets = Vec::new();
             let players_with_hand = Vec::new();
             let deck = Deck::new_with_capacity(nth_decks).map(|deck| Arc::new(Mutex::new(deck)))?;
 
+            let dealer = Player::new(DEALER_ID.to_string(), "Dealer".to_string());
+            let dealer = PlayerHand::new(Arc::new(dealer), deck.clone(), last_timestamp);
+
             let mut table = Self {
                 deck,
                 players_with_hand,
+                dealer,
+                soft_17: Soft17Rule::from_env(),
+                max_split_hands: max_split_hands_from_env(),
                 game,
                 round: 1,
                 id: generate_id(),
+                owner: None,
                 report: None,
             };
 
             table.game.players.iter().for_each(|player| {
+                let bet = table.game.get_bet(&player.get_id()).unwrap_or(0);
                 let player = player.clone();
-                let player_hand = PlayerHand::new(player, table.deck.clone(), last_timestamp);
+                let mut player_hand = PlayerHand::new(player, table.deck.clone(), last_timestamp);
+                player_hand.set_bet(bet);
                 table.players_with_hand.push(player_hand);
             });
 
-            // @TODO: Implement bet.
-
             Ok(table)
         }
 
@@ -394,6 +965,14 @@ pub mod prelude {
             &self.id
         }
 
+        pub fn get_owner(&self) -> Option<&str> {
+            self.owner.as_deref()
+        }
+
+        pub fn set_owner(&mut self, owner: &str) {
+            self.owner = Some(owner.to_string());
+        }
+
         pub fn get_name_player(&self, player_id: &str) -> Result<String, &'static str> {
             let player = self.get_player_by_id(player_id)?;
             Ok(player.get_player_ref().name.clone())
@@ -408,12 +987,6 @@ pub mod prelude {
             Ok(player.points)
         }
 
-        fn get_hand_ref(&mut self, player_id: &str) -> Option<&mut PlayerHand> {
-            self.players_with_hand
-                .iter_mut()
-                .find(|player| player_id == player.get_player_id())
-        }
-
         pub fn is_any_player_has_condition(&self, condition: fn(&PlayerHand) -> bool) -> bool {
             self.players_with_hand.iter().any(condition)
         }
@@ -422,24 +995,39 @@ pub mod prelude {
             self.players_with_hand.iter().all(condition)
         }
 
-        pub async fn hit_player(
-            &mut self,
-            player_id: &str,
-            timestamp: u64,
-            seed: &str,
-        ) -> Result<(), &'static str> {
-            let table_round = self.round;
-            let player = self.get_player_by_id_mut(player_id)?;
+        /**
+         * The player's hand if the player is in the table round.
+         */
+        pub fn get_player_in_round(&self, player_id: &str) -> Result<&PlayerHand, &'static str> {
+            let player = self.get_player_by_id(player_id)?;
             let player_round = player.get_round();
 
-            if table_round != player_round {
+            if self.round != player_round {
                 info!(
                     "Game round {}; Player round {}; Player id {};",
-                    table_round, player_round, player_id
+                    self.round, player_round, player_id
                 );
                 Err("Round is not the same. Waiting for another players.")?;
             }
 
+            Ok(player)
+        }
+
+        fn get_player_in_round_mut(
+            &mut self,
+            player_id: &str,
+        ) -> Result<&mut PlayerHand, &'static str> {
+            self.get_player_in_round(player_id)?;
+            self.get_player_by_id_mut(player_id)
+        }
+
+        pub async fn hit_player(
+            &mut self,
+            player_id: &str,
+            timestamp: u64,
+            seed: &str,
+        ) -> Result<(), &'static str> {
+            let player = self.get_player_in_round_mut(player_id)?;
             player.hit(timestamp, seed).await?;
 
             self.next_round();
@@ -449,6 +1037,241 @@ pub mod prelude {
             Ok(())
         }
 
+        /**
+         * Split the player's hand, the new hand is played right after it.
+         */
+        pub async fn split_player(
+            &mut self,
+            player_id: &str,
+            timestamp: u64,
+            seed: &str,
+            split_seed: &str,
+        ) -> Result<(), &'static str> {
+            self.can_split_player(player_id)?;
+            let index = self.get_active_hand_index(player_id)?;
+
+            let split = self.players_with_hand[index]
+                .split(timestamp, seed, split_seed)
+                .await?;
+            self.players_with_hand.insert(index + 1, split);
+
+            self.next_round();
+
+            self.regenerate_cache_hand();
+
+            Ok(())
+        }
+
+        /**
+         * The player's hand if it may be split now.
+         */
+        pub fn can_split_player(&self, player_id: &str) -> Result<&PlayerHand, &'static str> {
+            let hand = self.get_player_by_id(player_id)?;
+            hand.can_split()?;
+
+            if self.get_hands_by_id(player_id).len() >= self.max_split_hands {
+                Err("Split limit reached.")?;
+            }
+
+            self.get_player_in_round(player_id)
+        }
+
+        pub fn set_max_split_hands(&mut self, max_split_hands: usize) {
+            self.max_split_hands = max_split_hands;
+        }
+
+        pub async fn double_down_player(
+            &mut self,
+            player_id: &str,
+            timestamp: u64,
+            seed: &str,
+        ) -> Result<(), &'static str> {
+            let player = self.get_player_in_round_mut(player_id)?;
+            player.double_down(timestamp, seed).await?;
+
+            self.next_round();
+
+            self.regenerate_cache_hand();
+
+            Ok(())
+        }
+
+        /**
+         * Deal a card to the dealer, the second one is the hole card.
+         */
+        pub async fn hit_dealer(&mut self, timestamp: u64, seed: &str) -> Result<(), &'static str> {
+            self.dealer.hit(timestamp, seed).await?;
+            self.regenerate_cache_hand();
+            Ok(())
+        }
+
+        /**
+         * The dealer peeks at the hole card after the deal, a natural ends the round at once.
+         */
+        pub fn dealer_has_blackjack(&self) -> bool {
+            self.dealer.is_blackjack()
+        }
+
+        /**
+         * The dealer draws to 16 and stands on 17, hitting a soft 17 only under Soft17Rule::Hit.
+         */
+        pub fn dealer_should_hit(&self) -> bool {
+            let points = self.dealer.points;
+
+            points < 17
+                || (points == 17 && self.soft_17 == Soft17Rule::Hit && self.dealer.is_soft())
+        }
+
+        /**
+         * Every player finished and the dealer has nothing left to draw, the bets can be settled.
+         */
+        pub fn is_round_over(&self) -> bool {
+            !self.any_player_can_hit() && (self.dealer_has_blackjack() || !self.dealer_should_hit())
+        }
+
+        pub fn get_dealer(&self) -> &PlayerHand {
+            &self.dealer
+        }
+
+        pub fn set_soft_17_rule(&mut self, rule: Soft17Rule) {
+            self.soft_17 = rule;
+        }
+
+        #[cfg(any(test, feature = "test-helpers"))]
+        pub fn change_hand(
+            &mut self,
+            player_id: &str,
+            cards: Vec<crate::models::card::prelude::Card>,
+        ) -> Result<(), &'static str> {
+            let hand = self.get_player_by_id_mut(player_id)?;
+            hand.change_hand(cards);
+            Ok(())
+        }
+
+        #[cfg(any(test, feature = "test-helpers"))]
+        pub fn change_dealer_hand(&mut self, cards: Vec<crate::models::card::prelude::Card>) {
+            self.dealer.change_hand(cards);
+        }
+
+        fn outcome(&self, hand: &PlayerHand) -> Outcome {
+            let dealer_points = self.dealer.points;
+
+            if hand.is_surrendered() {
+                Outcome::Surrender
+            } else if hand.is_busted() {
+                Outcome::Bust
+            } else if hand.is_blackjack() != self.dealer.is_blackjack() {
+                // A natural beats any other hand, a 21 with more cards included
+                match hand.is_blackjack() {
+                    true => Outcome::Win,
+                    false => Outcome::Lose,
+                }
+            } else if self.dealer.is_busted() || hand.points > dealer_points {
+                Outcome::Win
+            } else if hand.points == dealer_points {
+                Outcome::Push
+            } else {
+                Outcome::Lose
+            }
+        }
+
+        /**
+         * Each player against the dealer, a busted player loses even if the dealer busts.
+         * A win pays 1:1, a natural 3:2, a push returns the bet and a surrender half of it.
+         */
+        pub fn settle(&self) -> Vec<Settlement> {
+            self.settle_by(|hand| self.outcome(hand))
+        }
+
+        /**
+         * Each player of a round stopped before it was over. Busted and surrendered hands keep
+         * their result, every other hand gets its bet back.
+         */
+        pub fn refund(&self) -> Vec<Settlement> {
+            self.settle_by(|hand| {
+                if hand.is_settled() {
+                    self.outcome(hand)
+                } else if hand.is_surrendered() {
+                    Outcome::Surrender
+                } else if hand.is_busted() {
+                    Outcome::Bust
+                } else {
+                    Outcome::Refund
+                }
+            })
+        }
+
+        /**
+         * Once the dealer peeked without a natural, every player's natural wins at once.
+         * Returns what to pay now, each hand is only settled once.
+         */
+        pub fn settle_naturals(&mut self) -> Vec<Settlement> {
+            if self.dealer_has_blackjack() {
+                return vec![];
+            }
+
+            let naturals = self.settle_by(|hand| self.outcome(hand));
+            let naturals = naturals
+                .into_iter()
+                .zip(self.players_with_hand.iter_mut())
+                .filter(|(_, hand)| hand.is_blackjack() && !hand.is_settled())
+                .map(|(settlement, hand)| {
+                    hand.set_settled();
+                    settlement
+                })
+                .collect::<Vec<_>>();
+
+            self.regenerate_cache_hand();
+            naturals
+        }
+
+        fn settle_by(&self, outcome: impl Fn(&PlayerHand) -> Outcome) -> Vec<Settlement> {
+            self.players_with_hand
+                .iter()
+                .map(|hand| {
+                    let outcome = outcome(hand);
+                    let bet = hand.get_bet();
+                    let payout = match outcome {
+                        Outcome::Win if hand.is_blackjack() => {
+                            bet.saturating_add(bet.saturating_mul(3) / 2)
+                        }
+                        Outcome::Win => bet.saturating_mul(2),
+                        Outcome::Push | Outcome::Refund => bet,
+                        Outcome::Surrender => bet / 2,
+                        Outcome::Lose | Outcome::Bust => 0,
+                    };
+
+                    Settlement {
+                        player: hand.get_player_ref(),
+                        outcome,
+                        points: hand.points,
+                        is_blackjack: hand.is_blackjack(),
+                        bet,
+                        payout,
+                        is_paid: hand.is_settled(),
+                    }
+                })
+                .collect()
+        }
+
+        /**
+         * Late surrender, the player gives up the hand before the first hit.
+         */
+        pub fn surrender_player(
+            &mut self,
+            player_id: &str,
+            last_timestamp: u64,
+        ) -> Result<(), &'static str> {
+            let player = self.get_player_in_round_mut(player_id)?;
+            player.surrender(last_timestamp)?;
+
+            self.next_round();
+
+            self.regenerate_cache_hand();
+
+            Ok(())
+        }
+
         pub fn stand_player(
             &mut self,
             player_id: &str,
@@ -463,43 +1286,107 @@ pub mod prelude {
         }
 
         fn next_round(&mut self) {
+            self.activate_split_hands();
+
             if self.can_advance_round() {
                 self.round += 1;
             }
         }
 
+        /**
+         * Split hands are played in sequence, the next hand starts at the round the previous one finished.
+         */
+        fn activate_split_hands(&mut self) {
+            let mut previous: Option<(String, u8)> = None;
+
+            for hand in self.players_with_hand.iter_mut() {
+                let player_id = hand.get_player_id();
+
+                if let Some((previous_id, round)) = &previous {
+                    if *previous_id == player_id && hand.get_round() < *round {
+                        hand.set_round(*round);
+                    }
+                }
+
+                previous = match hand.is_finished() {
+                    true => Some((player_id, hand.get_round())),
+                    false => None,
+                };
+            }
+        }
+
+        /**
+         * A split hand waiting for the player's previous hand to finish
+         */
+        fn is_waiting(&self, index: usize) -> bool {
+            let hand = &self.players_with_hand[index];
+            !hand.is_finished()
+                && self
+                    .get_active_hand_index(&hand.get_player_id())
+                    .is_ok_and(|active| active != index)
+        }
+
         pub fn any_player_can_hit(&self) -> bool {
             self.players_with_hand
                 .iter()
-                .any(|player| !player.get_status_stand())
+                .any(|player| !player.is_finished())
         }
 
         pub fn can_advance_round(&self) -> bool {
             info!("\nChecking if can advance round");
-            let result = self.players_with_hand.iter().all(|player| {
-                info!(
-                    "Player {} round {}; Table round {} is_standing {} points {}",
-                    player.get_name(),
-                    player.get_round(),
-                    self.round,
-                    player.get_status_stand(),
-                    player.points
-                );
-
-                player.get_status_stand() || self.round != player.get_round()
-            });
+            let result = self
+                .players_with_hand
+                .iter()
+                .enumerate()
+                .all(|(index, player)| {
+                    info!(
+                        "Player {} round {}; Table round {} is_standing {} points {}",
+                        player.get_name(),
+                        player.get_round(),
+                        self.round,
+                        player.get_status_stand(),
+                        player.points
+                    );
+
+                    player.is_finished()
+                        || self.round != player.get_round()
+                        || self.is_waiting(index)
+                });
             info!("Can advance {}\n", result);
             result
         }
 
+        /**
+         * The player's first hand still in play, or the last one when every hand is finished.
+         */
+        fn get_active_hand_index(&self, id: &str) -> Result<usize, &'static str> {
+            let mut last = None;
+
+            for (index, hand) in self.players_with_hand.iter().enumerate() {
+                if hand.get_player_id() == id {
+                    if !hand.is_finished() {
+                        return Ok(index);
+                    }
+                    last = Some(index);
+                }
+            }
+
+            last.ok_or("Player not found.")
+        }
+
         fn get_player_by_id_mut(&mut self, id: &str) -> Result<&mut PlayerHand, &'static str> {
+            let index = self.get_active_hand_index(id)?;
+            Ok(&mut self.players_with_hand[index])
+        }
+
+        pub fn get_hands_by_id(&self, id: &str) -> Vec<&PlayerHand> {
             self.players_with_hand
-                .iter_mut()
-                .find(|player| player.get_player_id() == id)
-                .ok_or("Player not found.")
+                .iter()
+                .filter(|player| player.get_player_id() == id)
+                .collect()
         }
 
-        #[cfg(test)]
+        #[cfg(any(test, feature = "test-helpers"))]
         pub fn change_points(&mut self, player_id: &str, points: u8) -> Result<(), &'static str> {
             let hand = self.get_player_by_id_mut(player_id)?;
             hand.points = points;
@@ -507,10 +1394,8 @@ pub mod prelude {
         }
 
         pub fn get_player_by_id(&self, id: &str) -> Result<&PlayerHand, &'static str> {
-            self.players_with_hand
-                .iter()
-                .find(|player| player.get_player_id() == id)
-                .ok_or("Player not found.")
+            let index = self.get_active_hand_index(id)?;
+            Ok(&self.players_with_hand[index])
         }
 
         pub fn regenerate_cache_hand(&mut self) {
@@ -529,54 +1414,54 @@ pub mod prelude {
             report
         }
 
+        /**
+         * The hole card stays hidden until every player stands.
+         */
         pub fn generate_hands(&self) -> Value {
+            self.generate_hands_with_dealer(!self.any_player_can_hit())
+        }
+
+        /**
+         * Hands for the scoreboard, the dealer is revealed even if the game was stopped.
+         */
+        pub fn generate_final_hands(&self) -> Value {
+            self.generate_hands_with_dealer(true)
+        }
+
+        fn generate_hands_with_dealer(&self, reveal: bool) -> Value {
+            let dealer = match reveal {
+                true => self.dealer.generate_hand(),
+                false => self.dealer.generate_hand_with_hole_card(),
+            };
+
             json!({
                 "game_id": self.game.get_id(),
                 "table_id": self.id,
                 "players": self.players_with_hand.iter().map(|player| player.generate_hand()).collect::<Vec<_>>(),
+                "dealer": dealer,
                 "is_finished": false,
                 "round":self.round,
             })
         }
 
         pub async fn get_winner(&self) -> Option<Arc<Player>> {
-            let mut winner: Option<Arc<Player>> = None;
-            let mut winner_points = 0;
-
             // Safe for check hands, anyone cant pick a card.
             let _deck = self.deck.lock().await;
-
-            for hand in self.players_with_hand.iter() {
-                if (winner.is_none() || hand.points > winner_points) && hand.points <= 21 {
-                    winner = Some(hand.get_player_ref());
-                    winner_points = hand.points;
-                } else if hand.points == winner_points {
-                    winner = None;
-                    break;
-                }
-            }
-
-            winner
+            self.best_hand_player()
         }
 
         pub fn get_winner_sync(&self) -> Option<Arc<Player>> {
-            let mut winner: Option<Arc<Player>> = None;
-            let mut winner_points = 0;
-
             // Safe for check hands, anyone cant pick a card.
             let _deck = self.deck.try_lock().ok()?;
+            self.best_hand_player()
+        }
 
-            for hand in self.players_with_hand.iter() {
-                if (winner.is_none() || hand.points > winner_points) && hand.points <= 21 {
-                    winner = Some(hand.get_player_ref());
-                    winner_points = hand.points;
-                } else if hand.points == winner_points {
-                    winner = None;
-                    break;
-                }
-            }
-
-            winner
+        /**
+         * The winner the scoreboard would report for the hands as they are now.
+         */
+        fn best_hand_player(&self) -> Option<Arc<Player>> {
+            let settlements = self.settle();
+            winner(&ranking(settlements.iter()))
         }
 
         pub fn has_player(&self, player_id: &str) -> bool {
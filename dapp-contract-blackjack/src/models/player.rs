@@ -6,9 +6,9 @@ pub mod prelude {
         sync::Arc,
     };
 
-    use log::info;
     use serde_json::{json, Value};
     use tokio::sync::Mutex;
+    use tracing::info;
 
     use crate::models::card::prelude::{Card, Deck, Rank};
 
@@ -77,6 +77,11 @@ pub mod prelude {
         is_standing: bool,
         deck: Arc<Mutex<Deck>>,
         round: u8,
+        bet: u32,
+        is_split: bool,
+        is_surrendered: bool,
+        // Paid at the dealer peek, before the round is over
+        is_settled: bool,
         pub last_timestamp: u64,
     }
 
@@ -100,6 +105,10 @@ pub mod prelude {
                 points: 0,
                 deck,
                 round: 1,
+                bet: 0,
+                is_split: false,
+                is_surrendered: false,
+                is_settled: false,
                 last_timestamp,
             }
         }
@@ -108,6 +117,25 @@ pub mod prelude {
             self.is_standing
         }
 
+        pub fn is_surrendered(&self) -> bool {
+            self.is_surrendered
+        }
+
+        pub fn is_settled(&self) -> bool {
+            self.is_settled
+        }
+
+        pub fn set_settled(&mut self) {
+            self.is_settled = true;
+        }
+
+        /**
+         * The hand takes no more cards, the player stood, busted or surrendered.
+         */
+        pub fn is_finished(&self) -> bool {
+            self.is_standing || self.is_surrendered
+        }
+
         pub fn generate_hand(&self) -> Value {
             let hand = self
                 .hand
@@ -122,6 +150,38 @@ pub mod prelude {
                 "hand": hand,
                 "is_standing": self.is_standing,
                 "is_busted": self.is_busted(),
+                "is_soft": self.is_soft(),
+                "is_blackjack": self.is_blackjack(),
+                "is_split": self.is_split,
+                "is_surrendered": self.is_surrendered,
+                "is_settled": self.is_settled,
+                "bet": self.bet,
+            })
+        }
+
+        /**
+         * Dealer's hand while players are still playing, only the first card is face up.
+         */
+        pub fn generate_hand_with_hole_card(&self) -> Value {
+            let hand = self
+                .hand
+                .0
+                .iter()
+                .enumerate()
+                .map(|(i, card)| match i {
+                    0 => card.serialize(),
+                    _ => "hidden".to_string(),
+                })
+                .collect::<Vec<_>>();
+
+            let points = self.hand.0.first().map_or(0, |card| card.show_point());
+
+            json!({
+                "name": self.player.name,
+                "points": points,
+                "hand": hand,
+                "is_standing": false,
+                "is_busted": false,
             })
         }
 
@@ -129,6 +189,33 @@ pub mod prelude {
             self.player.name.to_owned()
         }
 
+        pub fn get_hand_size(&self) -> usize {
+            self.hand.0.len()
+        }
+
+        pub fn get_bet(&self) -> u32 {
+            self.bet
+        }
+
+        pub fn set_bet(&mut self, bet: u32) {
+            self.bet = bet;
+        }
+
+        /**
+         * A natural, 21 with the first two cards of a hand that wasn't split.
+         */
+        pub fn is_blackjack(&self) -> bool {
+            !self.is_split && self.hand.0.len() == 2 && self.points == 21
+        }
+
+        pub fn is_split(&self) -> bool {
+            self.is_split
+        }
+
+        pub fn set_round(&mut self, round: u8) {
+            self.round = round;
+        }
+
         pub fn get_round(&self) -> u8 {
             self.round
         }
@@ -149,6 +236,43 @@ pub mod prelude {
             self.points
         }
 
+        /**
+         * Sum of the cards counting every ace as 1.
+         */
+        fn hard_points(&self) -> u8 {
+            self.hand
+                .0
+                .iter()
+                .map(|card| match card.rank {
+                    Rank::Ace => 1,
+                    _ => card.show_point(),
+                })
+                .sum()
+        }
+
+        /**
+         * An ace is still counted as 11 without busting the hand.
+         */
+        pub fn is_soft(&self) -> bool {
+            self.hand.0.iter().any(|card| card.rank == Rank::Ace) && self.hard_points() + 10 <= 21
+        }
+
+        /**
+         * Best total of the hand, one ace counts as 11 while the hand is soft.
+         */
+        fn best_points(&self) -> u8 {
+            match self.is_soft() {
+                true => self.hard_points() + 10,
+                false => self.hard_points(),
+            }
+        }
+
+        #[cfg(any(test, feature = "test-helpers"))]
+        pub fn change_hand(&mut self, cards: Vec<Card>) {
+            self.hand = Hand(cards);
+            self.points = self.best_points();
+        }
+
         /**
          * Take a card from the deck and add it to the player's hand.
          */
@@ -157,10 +281,24 @@ pub mod prelude {
                 Err("Player is busted.")?;
             }
 
+            if self.is_surrendered {
+                Err("Player surrendered.")?;
+            }
+
             if self.is_standing {
                 Err("Player is standing.")?;
             }
 
+            self.draw(seed).await?;
+            self.round += 1;
+            self.last_timestamp = timestamp;
+            Ok(())
+        }
+
+        /**
+         * Take a card from the deck without playing a round, as when a split hand gets its second card.
+         */
+        async fn draw(&mut self, seed: &str) -> Result<(), &'static str> {
             let card = {
                 let mut deck = self.deck.lock().await;
                 let size = deck.cards.len();
@@ -175,23 +313,15 @@ pub mod prelude {
                 deck.cards.remove(nth)
             };
 
-            let card_point = card.show_point();
-            let points = self.points + card_point;
-
-            if card.rank == Rank::Ace && points > 21 {
-                self.points = points - 10;
-            } else {
-                self.points = points;
-            }
-
-            self.is_standing = self.points >= 21;
             info!(
-                "Round {}; points {}; card {:}; Player {};",
-                self.round, self.points, card, self.player.name
+                "Round {}; card {:}; Player {};",
+                self.round, card, self.player.name
             );
             self.hand.0.push(card);
-            self.round += 1;
-            self.last_timestamp = timestamp;
+            self.points = self.best_points();
+
+            self.is_standing = self.points >= 21;
+            info!("Points {}; soft {};", self.points, self.is_soft());
             Ok(())
         }
 
@@ -204,46 +334,110 @@ pub mod prelude {
             Ok(())
         }
 
-        /**
-         * Double the bet and take one more card.
-         */
-        async fn double_down(&mut self) -> Result<(), &'static str> {
-            if self.is_standing {
+        pub fn can_double_down(&self) -> Result<(), &'static str> {
+            if self.is_finished() {
                 Err("Already standing.")?;
             }
 
-            todo!();
+            if self.hand.0.len() != 2 {
+                Err("Double down only on the first two cards.")?;
+            }
 
-            // let player = self.player.clone();
+            Ok(())
+        }
 
-            // let player = player.lock().await;
+        /**
+         * Double the bet and take exactly one more card, then stand.
+         */
+        pub async fn double_down(
+            &mut self,
+            timestamp: u64,
+            seed: &str,
+        ) -> Result<(), &'static str> {
+            self.can_double_down()?;
 
-            // let player_balance = player.balance.as_ref().ok_or("No balance.")?.amount;
-            // let player_bet = player.bet.as_ref().ok_or("No bet.")?.amount;
+            let double_bet = self.bet.checked_mul(2).ok_or("Could not double bet.")?;
 
-            // let double_bet = player_bet.checked_mul(2).ok_or("Could not double bet.")?;
+            self.hit(timestamp, seed).await?;
+            self.bet = double_bet;
+            self.is_standing = true;
+            Ok(())
+        }
 
-            // self.player.bet.as_mut().and_then(|credit| {
-            //     credit.amount = double_bet;
-            //     Some(credit)
-            // });
+        pub fn can_split(&self) -> Result<(), &'static str> {
+            if self.is_finished() {
+                Err("Already standing.")?;
+            }
 
-            // self.hit().await?;
-            // Ok(())
+            match self.hand.0.as_slice() {
+                [first, second] if first.rank == second.rank => Ok(()),
+                [_, _] => Err("Split only on cards of equal rank."),
+                _ => Err("Split only on the first two cards."),
+            }
         }
 
         /**
-         * Split the hand into two separate hands.
+         * Split the hand into two separate hands with the same bet, each one draws its second card.
+         * Split aces get that card only and stand.
          */
-        async fn split() {
-            todo!();
+        pub async fn split(
+            &mut self,
+            timestamp: u64,
+            seed: &str,
+            split_seed: &str,
+        ) -> Result<PlayerHand, &'static str> {
+            self.can_split()?;
+
+            let card = self
+                .hand
+                .0
+                .pop()
+                .ok_or("Split only on the first two cards.")?;
+            let is_aces = card.rank == Rank::Ace;
+
+            let mut split = PlayerHand::new(self.player.clone(), self.deck.clone(), timestamp);
+            split.hand.0.push(card);
+            split.points = split.best_points();
+            split.round = self.round;
+            split.bet = self.bet;
+            split.is_split = true;
+
+            self.points = self.best_points();
+            self.is_split = true;
+            self.last_timestamp = timestamp;
+
+            self.draw(seed).await?;
+            split.draw(split_seed).await?;
+
+            if is_aces {
+                self.is_standing = true;
+                split.is_standing = true;
+            }
+
+            Ok(split)
+        }
+
+        pub fn can_surrender(&self) -> Result<(), &'static str> {
+            if self.is_finished() {
+                Err("Already standing.")?;
+            }
+
+            if self.is_split || self.hand.0.len() != 2 {
+                Err("Surrender only before the first hit.")?;
+            }
+
+            Ok(())
         }
 
         /**
          * Give up the hand and lose half of the bet.
          */
-        async fn surrender() {
-            todo!();
+        pub fn surrender(&mut self, last_timestamp: u64) -> Result<(), &'static str> {
+            self.can_surrender()?;
+
+            self.is_surrendered = true;
+            self.last_timestamp = last_timestamp;
+            Ok(())
         }
     }
 }
@@ -1,10 +1,10 @@
 pub mod rollup {
     use dotenvy::var;
     use hyper::{body::to_bytes, header, Body, Client, Method, Request, StatusCode};
-    use log::{error, info, warn};
     use serde_json::{from_str, json, Value};
-    use std::{error::Error, str::from_utf8, sync::Arc, time::Duration};
+    use std::{error::Error, str::from_utf8, sync::Arc};
     use tokio::sync::Mutex;
+    use tracing::{error, info, info_span, warn, Instrument};
 
     use crate::{
         models::{
@@ -16,8 +16,11 @@ pub mod rollup {
                 decode_payload, generate_report, get_address_metadata_from_root, get_path_player,
                 get_path_player_name, load_json, write_json,
             },
-            pubkey::{call_update_key, DrandEnv},
+            logger::CORRELATION_ID_HEADER,
+            mode::RunMode,
+            portal::{chip_price, normalize_address, Portal},
             random::retrieve_seed,
+            voucher::{erc20_transfer, ether_withdrawal},
         },
     };
 
@@ -26,6 +29,8 @@ pub mod rollup {
 
         let client = Client::new();
         let server_addr = var("MIDDLEWARE_HTTP_SERVER_URL")?;
+        let mode = RunMode::from_env();
+        info!("Running in {:?} mode", mode);
 
         let mut status = "accept";
         loop {
@@ -43,37 +48,48 @@ pub mod rollup {
             if status_response == StatusCode::ACCEPTED {
                 warn!("No pending rollup request, trying again");
             } else {
+                let correlation_id = response
+                    .headers()
+                    .get(CORRELATION_ID_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
                 let body = to_bytes(response).await?;
                 let body = from_utf8(&body)?;
                 let body = from_str::<Value>(body)?;
 
                 let request_type = body["request_type"]
                     .as_str()
-                    .ok_or("request_type is not a string")?;
-
-                status = match request_type {
-                    "advance_state" => {
-                        handle_advance(manager.clone(), &server_addr[..], body).await?
-                    }
-                    "inspect_state" => {
-                        handle_inspect(manager.clone(), &server_addr[..], body).await?
-                    }
-                    &_ => {
-                        error!("Unknown request type");
-                        "reject"
+                    .ok_or("request_type is not a string")?
+                    .to_string();
+
+                let span = info_span!("input", request_type, correlation_id);
+
+                status = async {
+                    match request_type.as_str() {
+                        "advance_state" => {
+                            handle_advance(manager.clone(), &server_addr[..], body).await
+                        }
+                        "inspect_state" => {
+                            handle_inspect(manager.clone(), &server_addr[..], body).await
+                        }
+                        &_ => {
+                            error!("Unknown request type");
+                            Ok("reject")
+                        }
                     }
                 }
+                .instrument(span)
+                .await?;
             }
-            #[cfg(not(target_arch = "riscv64"))]
-            wait_func().await;
+            wait_func(mode).await;
         }
     }
 
-    pub async fn wait_func() {
-        #[cfg(not(target_arch = "riscv64"))]
-        {
-            warn!("waiting 5s...");
-            tokio::time::sleep(Duration::from_secs(5)).await;
+    pub async fn wait_func(mode: RunMode) {
+        if let Some(duration) = mode.wait_between_finish() {
+            warn!("waiting {}s...", duration.as_secs());
+            tokio::time::sleep(duration).await;
         }
     }
 
@@ -119,7 +135,7 @@ pub mod rollup {
         let req = hyper::Request::builder()
             .method(hyper::Method::POST)
             .header(hyper::header::CONTENT_TYPE, "application/json")
-            .uri(format!("{}/report", server_addr))
+            .uri(format!("{}/report", server_addr.trim_end_matches('/')))
             .body(hyper::Body::from(report.to_string()))?;
 
         let _ = client.request(req).await?;
@@ -132,7 +148,7 @@ pub mod rollup {
         let req = hyper::Request::builder()
             .method(hyper::Method::POST)
             .header(hyper::header::CONTENT_TYPE, "application/json")
-            .uri(format!("{}/notice", server_addr))
+            .uri(format!("{}/notice", server_addr.trim_end_matches('/')))
             .body(hyper::Body::from(notice.to_string()))?;
 
         let result = client.request(req).await?;
@@ -140,6 +156,24 @@ pub mod rollup {
         Ok(())
     }
 
+    pub async fn send_voucher(voucher: Value) -> Result<(), Box<dyn Error>> {
+        let server_addr = var("ROLLUP_HTTP_SERVER_URL")?;
+        let client = hyper::Client::new();
+        let req = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .uri(format!("{}/voucher", server_addr.trim_end_matches('/')))
+            .body(hyper::Body::from(voucher.to_string()))?;
+
+        let result = client.request(req).await?;
+        info!("Send voucher: {:?}", result.status());
+
+        if !result.status().is_success() {
+            return Err(format!("Voucher rejected with status {}", result.status()).into());
+        }
+        Ok(())
+    }
+
     pub fn get_payload_from_root<T>(root: &Value) -> Result<T, Box<dyn Error>>
     where
         T: serde::de::DeserializeOwned,
@@ -178,12 +212,46 @@ pub mod rollup {
         }
     }
 
+    async fn async_pick_dealer(table: Arc<Mutex<Table>>, timestamp: u64) {
+        info!("Dealer calling");
+        let seed = match retrieve_seed(timestamp).await {
+            Ok(seed) => seed,
+            Err(_) => return,
+        };
+
+        let result = table.lock().await.hit_dealer(timestamp, &seed).await;
+
+        if let Err(err) = result {
+            error!("Dealer pick error: {:}", err);
+        }
+    }
+
+    /**
+     * Once every player stands the dealer reveals the hole card and draws by the table rules.
+     */
+    async fn play_dealer(table: &mut Table, timestamp: u64) -> Result<(), &'static str> {
+        while table.dealer_should_hit() {
+            let seed = retrieve_seed(timestamp).await?;
+            table.hit_dealer(timestamp, &seed).await?;
+        }
+        info!("Dealer finished with {} points", table.get_dealer().points);
+        Ok(())
+    }
+
     pub async fn handle_request_action(
         root: &Value,
         manager: Arc<Mutex<Manager>>,
         write_hd_mode: bool,
     ) -> Result<Option<Value>, Box<dyn Error>> {
-        info!("Handling request action with root {}", root.to_string());
+        info!("Handling request action with root {}", root);
+
+        // Portals send ABI encoded deposits instead of JSON
+        let portal = get_address_metadata_from_root(root)
+            .and_then(|metadata| Portal::from_sender(&metadata.address));
+        if let Some(portal) = portal {
+            handle_deposit(root, portal, manager).await?;
+            return Ok(None);
+        }
 
         let payload = get_payload_from_root(root)?;
         let action = get_from_payload_action(&payload);
@@ -191,48 +259,6 @@ pub mod rollup {
         info!("Action: {:}", action.as_deref().unwrap_or("None"));
 
         match action.as_deref() {
-            Some("update_drand") => {
-                let input = payload.get("input").ok_or("Invalid field input")?;
-
-                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
-                let address_owner = metadata.address.trim_start_matches("0x").to_lowercase();
-
-                let address_owner_game =
-                    var("ADDRESS_OWNER_GAME").or(Err("Address owner game not defined"))?;
-
-                let address_owner_game = address_owner_game.trim_start_matches("0x").to_lowercase();
-
-                if address_owner != address_owner_game {
-                    return Err("Invalid owner".into());
-                }
-
-                // Parsing JSON
-                let public_key = input
-                    .get("public_key")
-                    .ok_or("Invalid field public_key")?
-                    .as_str()
-                    .ok_or("Invalid public_key")?;
-
-                let period = input.get("period").map(|v| v.as_u64()).unwrap_or(None);
-
-                let genesis_time = input
-                    .get("genesis_time")
-                    .map(|v| v.as_u64())
-                    .unwrap_or(None);
-
-                let safe_seconds = input
-                    .get("safe_seconds")
-                    .map(|v| v.as_u64())
-                    .unwrap_or(None);
-
-                let request_env = DrandEnv::new(public_key, period, genesis_time, safe_seconds);
-
-                let response = call_update_key(&request_env).await;
-
-                if response.is_err() {
-                    return Err("Could not update drand config".into());
-                }
-            }
             Some("new_player") => {
                 let input = payload.get("input").ok_or("Invalid field input")?;
                 let player_name = check_fields_create_player(input)?;
@@ -240,14 +266,15 @@ pub mod rollup {
                 let encoded_name = bs58::encode(&player_name).into_string();
 
                 let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
-                let address_owner = metadata.address.trim_start_matches("0x");
-                let address_encoded = bs58::encode(address_owner).into_string();
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
 
                 // Add player to manager
                 let player = Player::new(address_encoded.clone(), player_name.to_string());
                 let mut manager = manager.lock().await;
                 let player = Arc::new(player);
                 manager.add_player(player)?;
+                let balance = manager.credit_balance(&address_encoded, initial_balance())?;
 
                 // Persist player
                 if write_hd_mode {
@@ -270,6 +297,7 @@ pub mod rollup {
                     "address": address_encoded,
                     "encoded_name": encoded_name,
                     "name": player_name,
+                    "balance": balance,
                 }));
 
                 info!("Report: {:}", report);
@@ -281,8 +309,8 @@ pub mod rollup {
 
                 // Address
                 let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
-                let address_owner = metadata.address.trim_start_matches("0x");
-                let address_encoded = bs58::encode(address_owner).into_string();
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
 
                 // load to memory if not exists
                 if write_hd_mode {
@@ -342,12 +370,113 @@ pub mod rollup {
                     "address": address_owner,
                     "joined": joined,
                     "playing": playing,
+                    "balance": manager.get_balance(&address_encoded),
                 });
                 info!("player {:?}", player);
                 let report = generate_report(player);
 
                 return Ok(Some(report));
             }
+            Some("bet") => {
+                let input = payload.get("input").ok_or("Invalid field input")?;
+
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+
+                // Parsing JSON
+                let game_id = input
+                    .get("game_id")
+                    .ok_or("Invalid field game_id")?
+                    .as_str()
+                    .ok_or("Invalid game_id")?;
+
+                let amount = input
+                    .get("amount")
+                    .ok_or("Invalid field amount")?
+                    .as_u64()
+                    .and_then(|amount| u32::try_from(amount).ok())
+                    .ok_or("Invalid amount")?;
+
+                let mut manager = manager.lock().await;
+                manager.place_bet(game_id, &address_encoded, amount)?;
+
+                info!("Bet: {} on game_id {}", amount, game_id);
+            }
+            Some("withdraw") => {
+                let input = payload.get("input").ok_or("Invalid field input")?;
+
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+
+                // Parsing JSON
+                let chips = input
+                    .get("chips")
+                    .ok_or("Invalid field chips")?
+                    .as_u64()
+                    .and_then(|chips| u32::try_from(chips).ok())
+                    .filter(|chips| *chips > 0)
+                    .ok_or("Invalid chips")?;
+
+                let asset = input
+                    .get("asset")
+                    .map(|asset| asset.as_str().ok_or("Invalid asset"))
+                    .unwrap_or(Ok("ether"))?;
+
+                let token = match asset {
+                    "ether" => None,
+                    token => Some(normalize_address(token)),
+                };
+
+                // Named like the deposits, the chips bought with each asset are paid out in it
+                let asset = token
+                    .as_ref()
+                    .map_or("ether".to_string(), |token| format!("0x{}", token));
+
+                let amount = chip_price(token.as_deref())?
+                    .checked_mul(chips as u128)
+                    .ok_or("Withdrawal too large")?;
+
+                let voucher = match &token {
+                    None => {
+                        let dapp = var("ADDRESS_DAPP").or(Err("DApp address not defined"))?;
+                        ether_withdrawal(&dapp, &address_owner, amount)?
+                    }
+                    Some(token) => erc20_transfer(token, &address_owner, amount)?,
+                };
+
+                let balance = manager
+                    .lock()
+                    .await
+                    .withdraw(&address_encoded, &asset, chips)?;
+
+                // The chips come back if the voucher could not be emitted
+                if let Err(err) = send_voucher(voucher).await {
+                    manager
+                        .lock()
+                        .await
+                        .cancel_withdraw(&address_encoded, &asset, chips)?;
+                    return Err(err);
+                }
+
+                info!(
+                    "Withdraw: {} chips of {} by {}",
+                    chips, asset, address_owner
+                );
+
+                let report = generate_report(json!({
+                    "action": "withdraw",
+                    "asset": asset,
+                    "chips": chips,
+                    "amount": amount.to_string(),
+                    "balance": balance,
+                }));
+
+                return Ok(Some(report));
+            }
             Some("show_games") => {
                 let manager = manager.lock().await;
                 let report = Manager::generate_games_report(&manager.games);
@@ -359,6 +488,9 @@ pub mod rollup {
             Some("start_game") => {
                 let input = payload.get("input").ok_or("Invalid field input")?;
                 let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+                let is_game_owner = is_game_owner(&metadata.address);
                 let timestamp = metadata.timestamp;
 
                 // Parsing JSON
@@ -384,32 +516,65 @@ pub mod rollup {
                     return Err("Minimum number of players not reached.".into());
                 }
 
+                if !game.all_players_bet() {
+                    manager.add_game(game);
+                    return Err("Every player must bet before the game starts.".into());
+                }
+
+                // The table owner may stop the round, so only someone seated or the game owner opens it
+                if !is_game_owner && !game.has_player(&address_encoded) {
+                    manager.add_game(game);
+                    return Err("Only a player of the game can start it.".into());
+                }
+
                 let players = game.players.iter().map(|p| p.get_id()).collect::<Vec<_>>();
 
                 // Generate table from game
-                let table = game.round_start(2, metadata.timestamp)?;
+                let mut table = game.round_start(2, metadata.timestamp)?;
+                table.set_owner(&address_encoded);
                 let table = Arc::new(Mutex::from(table));
 
-                // Draw two cards for each player
+                // Draw two cards for each player and the dealer, the dealer's second is the hole card
                 for _ in 0..2 {
                     for player_id in players.iter() {
                         let table = table.clone();
                         let player_id = player_id.to_owned();
                         async_pick(table.clone(), player_id, timestamp).await;
                     }
+                    async_pick_dealer(table.clone(), timestamp).await;
                 }
 
                 let table = Arc::into_inner(table).ok_or("Could not get table")?;
-                let table = Mutex::into_inner(table);
+                let mut table = Mutex::into_inner(table);
                 let table_id = table.get_id().to_owned();
 
+                // Everybody may stand on 21 right after the deal, and a dealer's natural
+                // settles every hand at once, pushing only against the players' naturals
+                let is_finished = table.dealer_has_blackjack() || !table.any_player_can_hit();
+                if is_finished {
+                    play_dealer(&mut table, timestamp).await?;
+                }
+
                 // Add table to manager
                 manager.add_table(table);
                 info!("Game started: game_id {} table_id {}", game_id, table_id);
+
+                // The dealer peeked without a natural, the players' naturals are paid now
+                manager.settle_naturals(&table_id)?;
+
+                if is_finished {
+                    manager.stop_game(&table_id).await?;
+                }
             }
             Some("stop_game") => {
                 let input = payload.get("input").ok_or("Invalid field input")?;
 
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+                let is_game_owner = is_game_owner(&metadata.address);
+
                 // Parsing JSON
                 let game_id = input
                     .get("game_id")
@@ -419,7 +584,32 @@ pub mod rollup {
 
                 let mut manager = manager.lock().await;
 
-                manager.stop_game(game_id).await?;
+                match manager.get_table(game_id) {
+                    Some(table) => {
+                        // A round stopped halfway would be settled against the dealer's unplayed hand,
+                        // so only the game owner refunds it and no player walks away from a losing hand
+                        if table.is_round_over() {
+                            if !is_game_owner && table.get_owner() != Some(address_encoded.as_str())
+                            {
+                                return Err("Only the table owner can stop the game.".into());
+                            }
+                            manager.stop_game(game_id).await?;
+                        } else {
+                            if !is_game_owner {
+                                return Err(
+                                    "Only the game owner can cancel a running round.".into()
+                                );
+                            }
+                            manager.cancel_table(game_id).await?;
+                        }
+                    }
+                    None => {
+                        if !is_game_owner {
+                            return Err("Only the game owner can cancel a game.".into());
+                        }
+                        manager.cancel_game(game_id)?;
+                    }
+                }
             }
             Some("show_hands") => {
                 let input = payload.get("input").ok_or("Invalid field input")?;
@@ -457,8 +647,8 @@ pub mod rollup {
             Some("hit") => {
                 // Address
                 let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
-                let address_owner = metadata.address.trim_start_matches("0x");
-                let address_encoded = bs58::encode(address_owner).into_string();
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
                 let timestamp = metadata.timestamp;
 
                 // Table ID
@@ -476,9 +666,92 @@ pub mod rollup {
                 table.hit_player(&address_encoded, timestamp, &seed).await?;
 
                 if !table.any_player_can_hit() {
+                    play_dealer(table, timestamp).await?;
                     manager.stop_game(&table_id).await?;
                 }
             }
+            Some("double_down") => {
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+                let timestamp = metadata.timestamp;
+
+                // Table ID
+                let input = payload.get("input").ok_or("Invalid field input")?;
+                let table_id = input
+                    .get("table_id")
+                    .ok_or("Invalid field table_id")?
+                    .as_str()
+                    .ok_or("Invalid table_id")?;
+
+                let mut manager = manager.lock().await;
+                // An invalid action must not spend randomness
+                manager.can_double_down(table_id, &address_encoded)?;
+                let seed = retrieve_seed(timestamp).await?;
+                manager
+                    .double_down(table_id, &address_encoded, timestamp, &seed)
+                    .await?;
+
+                let table = manager.get_table_mut(table_id)?;
+                if !table.any_player_can_hit() {
+                    play_dealer(table, timestamp).await?;
+                    manager.stop_game(table_id).await?;
+                }
+            }
+            Some("split") => {
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+                let timestamp = metadata.timestamp;
+
+                // Table ID
+                let input = payload.get("input").ok_or("Invalid field input")?;
+                let table_id = input
+                    .get("table_id")
+                    .ok_or("Invalid field table_id")?
+                    .as_str()
+                    .ok_or("Invalid table_id")?;
+
+                let mut manager = manager.lock().await;
+                // An invalid action must not spend randomness
+                manager.can_split(table_id, &address_encoded)?;
+                let seed = retrieve_seed(timestamp).await?;
+                let split_seed = retrieve_seed(timestamp).await?;
+                manager
+                    .split(table_id, &address_encoded, timestamp, &seed, &split_seed)
+                    .await?;
+
+                let table = manager.get_table_mut(table_id)?;
+                if !table.any_player_can_hit() {
+                    play_dealer(table, timestamp).await?;
+                    manager.stop_game(table_id).await?;
+                }
+            }
+            Some("surrender") => {
+                // Address
+                let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
+
+                // Table ID
+                let input = payload.get("input").ok_or("Invalid field input")?;
+                let table_id = input
+                    .get("table_id")
+                    .ok_or("Invalid field table_id")?
+                    .as_str()
+                    .ok_or("Invalid table_id")?;
+
+                let mut manager = manager.lock().await;
+                let table = manager.get_table_mut(table_id)?;
+                table.surrender_player(&address_encoded, metadata.timestamp)?;
+
+                if !table.any_player_can_hit() {
+                    play_dealer(table, metadata.timestamp).await?;
+                    manager.stop_game(table_id).await?;
+                }
+            }
             Some("stand") => {
                 let input = payload.get("input").ok_or("Invalid field input")?;
 
@@ -490,17 +763,18 @@ pub mod rollup {
                     .ok_or("Invalid game_id")?;
 
                 let metadata = get_address_metadata_from_root(root).ok_or("Invalid address")?;
-                let address_owner = metadata.address.trim_start_matches("0x");
-                let address_encoded = bs58::encode(address_owner).into_string();
+                let address_owner = normalize_address(&metadata.address);
+                let address_encoded = bs58::encode(&address_owner).into_string();
 
                 let mut manager = manager.lock().await;
                 let table = manager.get_table_mut(game_id)?;
 
-                let name = table.get_name_player(&address_encoded).unwrap();
+                let name = table.get_name_player(&address_encoded)?;
                 let table_id = table.get_id().to_owned();
                 table.stand_player(&address_encoded, metadata.timestamp)?;
 
                 if !table.any_player_can_hit() {
+                    play_dealer(table, metadata.timestamp).await?;
                     manager.stop_game(&table_id).await?;
                 }
                 info!("Stand: {} game_id {}", name, game_id);
@@ -511,6 +785,71 @@ pub mod rollup {
         Ok(None)
     }
 
+    /**
+     * Credit the depositor with the chips bought and confirm it with a notice
+     */
+    async fn handle_deposit(
+        root: &Value,
+        portal: Portal,
+        manager: Arc<Mutex<Manager>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let payload = root["data"]["payload"].as_str().ok_or("Invalid payload")?;
+        let payload = hex::decode(payload.trim_start_matches("0x"))?;
+
+        let deposit = match portal.decode(&payload)? {
+            Some(deposit) => deposit,
+            None => {
+                warn!("Failed {:?} deposit, nothing to credit", portal);
+                return Ok(());
+            }
+        };
+
+        let price = deposit.chip_price()?;
+        let asset = deposit.asset();
+        let address_encoded = bs58::encode(&deposit.depositor).into_string();
+
+        let (chips, balance) = {
+            let mut manager = manager.lock().await;
+            let chips = manager.deposit(&address_encoded, &asset, deposit.amount, price)?;
+            (chips, manager.get_balance(&address_encoded))
+        };
+
+        info!(
+            "Deposit: {} of {} by {} bought {} chips",
+            deposit.amount, asset, deposit.depositor, chips
+        );
+
+        let notice = generate_report(json!({
+            "action": "deposit",
+            "address": format!("0x{}", deposit.depositor),
+            "asset": asset,
+            "amount": deposit.amount.to_string(),
+            "chips": chips,
+            "balance": balance,
+        }));
+        send_notice(notice).await?;
+
+        Ok(())
+    }
+
+    /**
+     * Whether the sender is ADDRESS_OWNER_GAME, allowed to stop any table or game
+     */
+    fn is_game_owner(address: &str) -> bool {
+        var("ADDRESS_OWNER_GAME")
+            .is_ok_and(|owner| normalize_address(&owner) == normalize_address(address))
+    }
+
+    /**
+     * Chips given to a new player, from the INITIAL_BALANCE env (0 by default)
+     */
+    fn initial_balance() -> u32 {
+        var("INITIAL_BALANCE")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0)
+    }
+
     async fn load_player_to_mem(
         manager: &Arc<Mutex<Manager>>,
         address_encoded: &str,
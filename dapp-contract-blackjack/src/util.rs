@@ -6,7 +6,7 @@ pub mod random {
     use std::{error::Error, ops::Range};
 
     use dotenvy::var;
-    use log::{error, info};
+    use tracing::{error, info};
 
     use hyper::{
         body::{self},
@@ -41,7 +41,12 @@ pub mod random {
         let response = client.request(request).await?;
 
         let status_response = response.status();
-        info!("Receive random status {}", &status_response);
+        let correlation_id = response
+            .headers()
+            .get(super::logger::CORRELATION_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        info!(correlation_id, "Receive random status {}", &status_response);
 
         match status_response {
             StatusCode::BAD_REQUEST => {
@@ -99,12 +104,12 @@ pub mod random {
 pub mod json {
     use std::path::PathBuf;
 
-    use log::info;
     use serde_json::{json, Value};
     use tokio::{
         fs::{read_to_string, File},
         io::{self, AsyncWriteExt},
     };
+    use tracing::info;
 
     use super::Metadata;
 
@@ -184,103 +189,302 @@ pub mod json {
     }
 }
 
-pub mod pubkey {
-    use std::error::Error;
-
+pub mod portal {
     use dotenvy::var;
-    use hyper::{Body, Client, Method, Request};
-    use log::{error, info};
-
-    #[derive(serde::Deserialize, serde::Serialize)]
-    #[allow(non_snake_case)]
-    pub struct DrandEnv {
-        pub DRAND_PUBLIC_KEY: String,
-        pub DRAND_PERIOD: Option<u64>,
-        pub DRAND_GENESIS_TIME: Option<u64>,
-        pub DRAND_SAFE_SECONDS: Option<u64>,
+
+    /**
+     * Value locked in the DApp by a portal, the amount in the asset's smallest unit.
+     */
+    #[derive(Debug, PartialEq)]
+    pub struct Deposit {
+        pub depositor: String,
+        pub token: Option<String>,
+        pub amount: u128,
     }
 
-    impl DrandEnv {
-        pub fn new(
-            pubkey: &str,
-            period: Option<u64>,
-            genesis_time: Option<u64>,
-            safe_seconds: Option<u64>,
-        ) -> Self {
-            Self {
-                DRAND_PUBLIC_KEY: pubkey.to_owned(),
-                DRAND_PERIOD: period,
-                DRAND_GENESIS_TIME: genesis_time,
-                DRAND_SAFE_SECONDS: safe_seconds,
-            }
+    impl Deposit {
+        /**
+         * "ether" or the 0x token address.
+         */
+        pub fn asset(&self) -> String {
+            self.token
+                .as_ref()
+                .map_or("ether".to_string(), |token| format!("0x{}", token))
+        }
+
+        pub fn chip_price(&self) -> Result<u128, &'static str> {
+            chip_price(self.token.as_deref())
         }
     }
 
-    pub async fn call_update_key(drand_env: &DrandEnv) -> Result<(), Box<dyn Error>> {
-        let body = serde_json::to_string(drand_env)?;
+    /**
+     * Smallest units of ether, or of a token, bought by one chip, from CHIP_PRICE_ETHER
+     * or, for the token in ERC20_TOKEN only, from CHIP_PRICE_ERC20.
+     */
+    pub fn chip_price(token: Option<&str>) -> Result<u128, &'static str> {
+        let price = match token {
+            None => var("CHIP_PRICE_ETHER").or(Err("Chip price in ether not defined"))?,
+            Some(token) => {
+                let accepted = var("ERC20_TOKEN").or(Err("ERC-20 token not defined"))?;
+                if normalize_address(&accepted) != normalize_address(token) {
+                    return Err("ERC-20 token not accepted");
+                }
+                var("CHIP_PRICE_ERC20").or(Err("Chip price in ERC-20 not defined"))?
+            }
+        };
 
-        let client = Client::new();
-        let server_addr = var("MIDDLEWARE_HTTP_SERVER_URL")?;
-        let server_addr = server_addr.trim_end_matches('/');
+        match price.parse::<u128>() {
+            Ok(price) if price > 0 => Ok(price),
+            _ => Err("Invalid chip price"),
+        }
+    }
 
-        let uri = format!("{}/update_drand_config", &server_addr);
+    /**
+     * Portals of the Cartesi Rollups, recognized by the msg_sender of the input.
+     */
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Portal {
+        Ether,
+        Erc20,
+    }
 
-        info!("Calling update key at {:}", &uri);
+    impl Portal {
+        /**
+         * The portal whose address, from ADDRESS_ETHER_PORTAL or ADDRESS_ERC20_PORTAL, sent the input
+         */
+        pub fn from_sender(sender: &str) -> Option<Portal> {
+            let sender = normalize_address(sender);
+            let is_sender =
+                |key: &str| var(key).is_ok_and(|address| normalize_address(&address) == sender);
+
+            if is_sender("ADDRESS_ETHER_PORTAL") {
+                Some(Portal::Ether)
+            } else if is_sender("ADDRESS_ERC20_PORTAL") {
+                Some(Portal::Erc20)
+            } else {
+                None
+            }
+        }
 
-        let request = Request::builder()
-            .method(Method::PUT)
-            .uri(&uri)
-            .header("Content-Type", "application/json")
-            .body(Body::from(body))?;
+        /**
+         * Payloads are packed, the EtherPortal sends sender (20 bytes), value (32 bytes) and extra data,
+         * the ERC20Portal sends success (1 byte), token (20 bytes), sender (20 bytes), amount (32 bytes) and extra data.
+         * A failed ERC-20 transfer is not a deposit.
+         */
+        pub fn decode(&self, payload: &[u8]) -> Result<Option<Deposit>, &'static str> {
+            match self {
+                Portal::Ether => {
+                    let depositor = payload.get(0..20).ok_or("Invalid ether deposit")?;
+                    let amount = payload.get(20..52).ok_or("Invalid ether deposit")?;
+
+                    Ok(Some(Deposit {
+                        depositor: hex::encode(depositor),
+                        token: None,
+                        amount: decode_uint(amount)?,
+                    }))
+                }
+                Portal::Erc20 => {
+                    let success = payload.first().ok_or("Invalid ERC-20 deposit")?;
+                    let token = payload.get(1..21).ok_or("Invalid ERC-20 deposit")?;
+                    let depositor = payload.get(21..41).ok_or("Invalid ERC-20 deposit")?;
+                    let amount = payload.get(41..73).ok_or("Invalid ERC-20 deposit")?;
+
+                    if *success != 1 {
+                        return Ok(None);
+                    }
+
+                    Ok(Some(Deposit {
+                        depositor: hex::encode(depositor),
+                        token: Some(hex::encode(token)),
+                        amount: decode_uint(amount)?,
+                    }))
+                }
+            }
+        }
+    }
 
-        let response = client.request(request).await?;
+    /**
+     * Lowercase hex without 0x, like the addresses in the decoded payloads
+     */
+    pub fn normalize_address(address: &str) -> String {
+        address.trim_start_matches("0x").to_lowercase()
+    }
+
+    /**
+     * A big endian uint256, that must fit in 128 bits
+     */
+    fn decode_uint(word: &[u8]) -> Result<u128, &'static str> {
+        let (high, low) = word.split_at(16);
 
-        if response.status().is_success() {
-            info!("Update key success");
-            Ok(())
-        } else {
-            let msg = "Update key failed";
-            error!("{}", msg);
-            Err(msg.into())
+        if high.iter().any(|byte| *byte != 0) {
+            return Err("Amount too large");
         }
+
+        let low = low.try_into().or(Err("Invalid amount"))?;
+        Ok(u128::from_be_bytes(low))
     }
 }
 
-#[cfg(test)]
-pub mod env {
-    #[allow(unused_macros)]
-    macro_rules! check_if_dotenv_is_loaded {
-        () => {{
-            let is_env_loaded = dotenvy::dotenv().ok().is_some();
-            assert!(is_env_loaded);
-            is_env_loaded
-        }};
+pub mod voucher {
+    use serde_json::{json, Value};
+
+    use super::portal::normalize_address;
+
+    /**
+     * transfer(address,uint256) of ERC-20 tokens
+     */
+    pub const ERC20_TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+    /**
+     * withdrawEther(address,uint256) of the CartesiDApp contract
+     */
+    pub const WITHDRAW_ETHER: [u8; 4] = [0x52, 0x2f, 0x68, 0x15];
+
+    /**
+     * An address as an ABI word, left padded with zeros
+     */
+    pub fn encode_address(address: &str) -> Result<[u8; 32], &'static str> {
+        let address = hex::decode(normalize_address(address)).or(Err("Invalid address"))?;
+        if address.len() != 20 {
+            return Err("Invalid address");
+        }
+
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(&address);
+        Ok(word)
+    }
+
+    /**
+     * A uint256 as an ABI word, big endian
+     */
+    pub fn encode_uint(value: u128) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[16..].copy_from_slice(&value.to_be_bytes());
+        word
+    }
+
+    /**
+     * Calldata of a function taking (address, uint256)
+     */
+    pub fn encode_call(
+        selector: [u8; 4],
+        receiver: &str,
+        amount: u128,
+    ) -> Result<Vec<u8>, &'static str> {
+        Ok([
+            &selector[..],
+            &encode_address(receiver)?,
+            &encode_uint(amount),
+        ]
+        .concat())
+    }
+
+    fn generate_voucher(destination: &str, payload: Vec<u8>) -> Result<Value, &'static str> {
+        encode_address(destination)?;
+
+        Ok(json!({
+            "destination": format!("0x{}", normalize_address(destination)),
+            "payload": format!("0x{}", hex::encode(payload)),
+        }))
+    }
+
+    /**
+     * Voucher calling the token to transfer `amount` to the receiver
+     */
+    pub fn erc20_transfer(
+        token: &str,
+        receiver: &str,
+        amount: u128,
+    ) -> Result<Value, &'static str> {
+        generate_voucher(token, encode_call(ERC20_TRANSFER, receiver, amount)?)
+    }
+
+    /**
+     * Voucher calling the DApp contract to send `amount` wei to the receiver
+     */
+    pub fn ether_withdrawal(
+        dapp: &str,
+        receiver: &str,
+        amount: u128,
+    ) -> Result<Value, &'static str> {
+        generate_voucher(dapp, encode_call(WITHDRAW_ETHER, receiver, amount)?)
     }
-    #[allow(unused_imports)]
-    pub(crate) use check_if_dotenv_is_loaded;
 }
 
-pub mod logger {
-    use log::{set_boxed_logger, Level, Log, Metadata, Record, SetLoggerError};
+pub mod mode {
+    use std::{str::FromStr, time::Duration};
 
-    pub struct SimpleLogger;
+    use dotenvy::var;
+    use tracing::warn;
+
+    /**
+     * How the DApp is running, chosen by the RUN_MODE env (production, host or test).
+     * Only host mode waits between finish calls, inside the Cartesi Machine finish blocks.
+     */
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub enum RunMode {
+        Production,
+        #[default]
+        Host,
+        Test,
+    }
 
-    impl Log for SimpleLogger {
-        fn enabled(&self, metadata: &Metadata) -> bool {
-            metadata.level() <= Level::Info
+    impl FromStr for RunMode {
+        type Err = String;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            match value.to_lowercase().as_str() {
+                "production" => Ok(RunMode::Production),
+                "host" => Ok(RunMode::Host),
+                "test" => Ok(RunMode::Test),
+                _ => Err(format!("Unknown run mode {}", value)),
+            }
         }
+    }
 
-        fn log(&self, record: &Record) {
-            println!("DAPP CONTRACT {} - {}", record.level(), record.args());
+    impl RunMode {
+        pub fn from_env() -> RunMode {
+            match var("RUN_MODE") {
+                Ok(value) => value.parse().unwrap_or_else(|e| {
+                    warn!("{}, using host mode", e);
+                    RunMode::Host
+                }),
+                Err(_) => RunMode::default(),
+            }
         }
 
-        fn flush(&self) {}
+        pub fn wait_between_finish(&self) -> Option<Duration> {
+            match self {
+                RunMode::Host => Some(Duration::from_secs(5)),
+                RunMode::Production | RunMode::Test => None,
+            }
+        }
     }
+}
 
-    impl SimpleLogger {
-        pub fn init() -> Result<(), SetLoggerError> {
-            let logger = Box::new(SimpleLogger);
-            set_boxed_logger(logger).map(|()| log::set_max_level(log::LevelFilter::Info))
+pub mod logger {
+    use dotenvy::var;
+    use tracing_subscriber::EnvFilter;
+
+    /**
+     * Header sent by the middleware with the epoch and input index of the input being handled
+     */
+    pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
+    /**
+     * Log level comes from RUST_LOG (info by default), LOG_FORMAT=json switches to JSON lines
+     */
+    pub fn init() {
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+        let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+        let result = match var("LOG_FORMAT").as_deref() {
+            Ok("json") => builder.json().with_current_span(true).try_init(),
+            _ => builder.without_time().try_init(),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Logger already set: {}", e);
         }
     }
 }
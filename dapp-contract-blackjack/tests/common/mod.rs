@@ -1,8 +1,20 @@
+#[cfg(test)]
+pub mod env {
+    macro_rules! check_if_dotenv_is_loaded {
+        () => {{
+            let is_env_loaded = dotenvy::dotenv().ok().is_some();
+            assert!(is_env_loaded);
+            is_env_loaded
+        }};
+    }
+    pub(crate) use check_if_dotenv_is_loaded;
+}
+
 #[cfg(test)]
 pub mod prelude {
     use httptest::{
         all_of,
-        matchers::{contains, key, request, url_decoded, Matcher},
+        matchers::{contains, eq, json_decoded, key, request, url_decoded, Matcher},
         responders::{json_encoded, status_code, Responder},
         Expectation, ServerPool,
     };
@@ -13,32 +25,42 @@ pub mod prelude {
         sync::Once,
     };
 
-    use crate::util::json::generate_message;
+    use dapp_contract_blackjack::util::json::generate_message;
 
     static BIND_SERVER: Once = Once::new();
     static SERVER_POOL: ServerPool = ServerPool::new(1);
 
-    pub async fn setup_change_key() -> impl Drop {
+    pub async fn setup_hit_random() -> impl Drop {
+        let message = generate_message(Value::from("blackjack"));
+
         add_expectation(
-            all_of![request::method_path(
-                hyper::Method::PUT.as_str(),
-                "/update_drand_config"
-            ),],
-            status_code(204),
+            all_of![
+                request::method_path(hyper::Method::GET.as_str(), "/random"),
+                request::query(url_decoded(contains(key("timestamp"))))
+            ],
+            json_encoded(message),
+        )
+        .await
+    }
+
+    pub async fn setup_notice() -> impl Drop {
+        add_expectation(
+            request::method_path(hyper::Method::POST.as_str(), "/notice"),
+            status_code(201),
         )
         .await
     }
 
-    pub async fn setup_dont_change_key() -> impl Drop {
+    /**
+     * The test fails if the DApp asks for randomness.
+     */
+    pub async fn setup_no_random() -> impl Drop {
         let server = SERVER_POOL.get_server();
 
         server.expect(
-            Expectation::matching(request::method_path(
-                hyper::Method::PUT.as_str(),
-                "/update_drand_config",
-            ))
-            .times(0)
-            .respond_with(status_code(500)),
+            Expectation::matching(request::method_path(hyper::Method::GET.as_str(), "/random"))
+                .times(0)
+                .respond_with(status_code(500)),
         );
 
         let url = server.url_str("");
@@ -48,25 +70,34 @@ pub mod prelude {
             set_var("ROLLUP_HTTP_SERVER_URL", &url);
         });
 
-        assert!(var("MIDDLEWARE_HTTP_SERVER_URL").is_ok());
-        assert!(var("ROLLUP_HTTP_SERVER_URL").is_ok());
-
-        println!("Server listening on {}", &url);
-
         server
     }
 
-    pub async fn setup_hit_random() -> impl Drop {
-        let message = generate_message(Value::from("blackjack"));
+    /**
+     * Every voucher must be sent exactly once.
+     */
+    pub async fn setup_vouchers(vouchers: Vec<Value>) -> impl Drop {
+        let server = SERVER_POOL.get_server();
 
-        add_expectation(
-            all_of![
-                request::method_path(hyper::Method::GET.as_str(), "/random"),
-                request::query(url_decoded(contains(key("timestamp"))))
-            ],
-            json_encoded(message),
-        )
-        .await
+        for voucher in vouchers {
+            server.expect(
+                Expectation::matching(all_of![
+                    request::method_path(hyper::Method::POST.as_str(), "/voucher"),
+                    request::body(json_decoded(eq(voucher))),
+                ])
+                .times(1)
+                .respond_with(status_code(201)),
+            );
+        }
+
+        let url = server.url_str("");
+
+        BIND_SERVER.call_once(|| {
+            set_var("MIDDLEWARE_HTTP_SERVER_URL", &url);
+            set_var("ROLLUP_HTTP_SERVER_URL", &url);
+        });
+
+        server
     }
 
     pub async fn add_expectation(
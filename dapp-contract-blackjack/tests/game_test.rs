@@ -1,12 +1,180 @@
-#[path = "../src/mod.rs"]
-mod imports;
-use imports::*;
-
 #[cfg(test)]
 mod game_tests {
     use std::sync::Arc;
 
-    use crate::models::{game::prelude::Manager, player::prelude::Player};
+    use serde_json::json;
+    use tokio::sync::Mutex;
+
+    use dapp_contract_blackjack::models::{
+        card::prelude::{Card, Deck, Rank, Suit},
+        game::prelude::{BetLimits, Manager, Outcome, Soft17Rule, Table},
+        player::prelude::{Player, PlayerHand},
+    };
+
+    const RANKS: [Rank; 13] = [
+        Rank::Ace,
+        Rank::Two,
+        Rank::Three,
+        Rank::Four,
+        Rank::Five,
+        Rank::Six,
+        Rank::Seven,
+        Rank::Eight,
+        Rank::Nine,
+        Rank::Ten,
+        Rank::Jack,
+        Rank::Queen,
+        Rank::King,
+    ];
+
+    fn cards(ranks: &[Rank]) -> Vec<Card> {
+        ranks
+            .iter()
+            .map(|rank| Card {
+                suit: Suit::Spades,
+                rank: rank.clone(),
+            })
+            .collect()
+    }
+
+    /**
+     * Hit the ranks in order from a deck holding only the next card, until the hand stands.
+     */
+    async fn deal(ranks: &[Rank]) -> PlayerHand {
+        let deck = Arc::new(Mutex::new(Deck { cards: vec![] }));
+        let player = Arc::new(Player::new_without_id("Alice".to_string()));
+        let mut hand = PlayerHand::new(player, deck.clone(), 0);
+
+        for card in cards(ranks) {
+            if hand.get_status_stand() {
+                break;
+            }
+            deck.lock().await.cards = vec![card];
+            hand.hit(0, "blackjack").await.unwrap();
+        }
+        hand
+    }
+
+    /**
+     * Naive count: every ace is tried both as 1 and as 11, the best total is the highest one
+     * not over 21, or the lowest when every total busts. Soft when that total counts an ace as 11.
+     */
+    fn naive_points(ranks: &[Rank]) -> (u8, bool) {
+        let values = |rank: &Rank| -> &[u8] {
+            match rank {
+                Rank::Ace => &[1, 11],
+                Rank::Two => &[2],
+                Rank::Three => &[3],
+                Rank::Four => &[4],
+                Rank::Five => &[5],
+                Rank::Six => &[6],
+                Rank::Seven => &[7],
+                Rank::Eight => &[8],
+                Rank::Nine => &[9],
+                Rank::Ten | Rank::Jack | Rank::Queen | Rank::King => &[10],
+            }
+        };
+
+        let mut totals = vec![(0u8, false)];
+        for rank in ranks {
+            totals = totals
+                .iter()
+                .flat_map(|&(total, soft)| {
+                    values(rank)
+                        .iter()
+                        .map(move |&value| (total + value, soft || value == 11))
+                })
+                .collect();
+        }
+
+        let best = totals.iter().filter(|(total, _)| *total <= 21).max();
+        let lowest = totals.iter().min();
+        *best.or(lowest).unwrap()
+    }
+
+    #[tokio::test]
+    async fn hand_valuation_cases() {
+        let cases: [(&[Rank], u8, bool, bool); 12] = [
+            (&[Rank::Ace, Rank::Six, Rank::Nine], 16, false, false),
+            (&[Rank::Ace, Rank::Six], 17, true, false),
+            (&[Rank::Ace, Rank::Ace], 12, true, false),
+            (&[Rank::Ace, Rank::Ace, Rank::Nine], 21, true, false),
+            (
+                &[Rank::Ace, Rank::Ace, Rank::Ace, Rank::Ace],
+                14,
+                true,
+                false,
+            ),
+            (&[Rank::Ace, Rank::King], 21, true, true),
+            (&[Rank::Queen, Rank::Ace], 21, true, true),
+            (&[Rank::Ace, Rank::Five, Rank::Five], 21, true, false),
+            (&[Rank::Nine, Rank::Ace, Rank::Ace], 21, true, false),
+            (&[Rank::Ten, Rank::Six, Rank::Ace], 17, false, false),
+            (
+                &[Rank::Ten, Rank::Six, Rank::Ace, Rank::Five],
+                22,
+                false,
+                false,
+            ),
+            (&[Rank::King, Rank::Queen, Rank::Two], 22, false, false),
+        ];
+
+        for (ranks, points, is_soft, is_blackjack) in cases {
+            let hand = deal(ranks).await;
+            let json = hand.generate_hand();
+            assert_eq!(json["points"], points, "{}", json);
+            assert_eq!(json["is_soft"], is_soft, "{}", json);
+            assert_eq!(json["is_blackjack"], is_blackjack, "{}", json);
+            assert_eq!(json["is_busted"], points > 21, "{}", json);
+        }
+    }
+
+    #[tokio::test]
+    async fn hand_valuation_every_rank_combination() {
+        for first in RANKS {
+            for second in RANKS {
+                let pair = [first.clone(), second.clone()];
+                let hand = deal(&pair).await;
+                let (points, is_soft) = naive_points(&pair);
+                assert_eq!(hand.get_points(), points);
+                assert_eq!(hand.is_soft(), is_soft);
+                assert_eq!(hand.is_blackjack(), points == 21);
+
+                for third in RANKS {
+                    let ranks = [first.clone(), second.clone(), third.clone()];
+                    let hand = deal(&ranks).await;
+                    // a natural stands on the first two cards
+                    let dealt = if points >= 21 {
+                        &ranks[..2]
+                    } else {
+                        &ranks[..]
+                    };
+                    let (points, is_soft) = naive_points(dealt);
+                    assert_eq!(hand.get_hand_size(), dealt.len());
+                    assert_eq!(hand.get_points(), points);
+                    assert_eq!(hand.is_soft(), is_soft);
+                    assert_eq!(hand.is_busted(), points > 21);
+                }
+            }
+        }
+    }
+
+    fn table_with_players(names: &[&str]) -> (Table, Vec<String>) {
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        let game_id = game.get_id().to_owned();
+
+        let mut ids = vec![];
+        for name in names {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        (game.round_start(1, 0).unwrap(), ids)
+    }
 
     #[tokio::test]
     async fn get_winner_tests() {
@@ -78,4 +246,562 @@ mod game_tests {
             assert!(winner.is_none());
         }
     }
+
+    #[tokio::test]
+    async fn settle_each_player_against_dealer() {
+        let (mut table, ids) = table_with_players(&["Alice", "Bob", "Carol", "Dave"]);
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Nine]));
+
+        table.change_points(&ids[0], 20).unwrap();
+        table.change_points(&ids[1], 19).unwrap();
+        table.change_points(&ids[2], 18).unwrap();
+        table.change_points(&ids[3], 22).unwrap();
+
+        let outcomes = table
+            .settle()
+            .into_iter()
+            .map(|settlement| (settlement.player.name.clone(), settlement.outcome))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            outcomes,
+            [
+                ("Alice".to_string(), Outcome::Win),
+                ("Bob".to_string(), Outcome::Push),
+                ("Carol".to_string(), Outcome::Lose),
+                ("Dave".to_string(), Outcome::Bust),
+            ]
+        );
+
+        // a busted dealer pays everybody still standing
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Six, Rank::Nine]));
+        let outcomes = table
+            .settle()
+            .into_iter()
+            .map(|settlement| settlement.outcome)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [Outcome::Win, Outcome::Win, Outcome::Win, Outcome::Bust]
+        );
+    }
+
+    #[tokio::test]
+    async fn dealer_soft_17_rule() {
+        assert_eq!("hit".parse(), Ok(Soft17Rule::Hit));
+        assert_eq!("Stand".parse(), Ok(Soft17Rule::Stand));
+        assert!("double".parse::<Soft17Rule>().is_err());
+
+        let (mut table, _) = table_with_players(&["Alice", "Bob"]);
+        assert!(table.dealer_should_hit());
+
+        table.change_dealer_hand(cards(&[Rank::Ten, Rank::Six]));
+        assert!(table.dealer_should_hit());
+
+        table.change_dealer_hand(cards(&[Rank::Ace, Rank::Six]));
+        assert!(table.get_dealer().is_soft());
+        table.set_soft_17_rule(Soft17Rule::Stand);
+        assert!(!table.dealer_should_hit());
+        table.set_soft_17_rule(Soft17Rule::Hit);
+        assert!(table.dealer_should_hit());
+
+        table.change_dealer_hand(cards(&[Rank::Ten, Rank::Seven]));
+        assert!(!table.get_dealer().is_soft());
+        assert!(!table.dealer_should_hit());
+
+        // both aces as 1 plus a ten is a hard 12
+        table.change_dealer_hand(cards(&[Rank::Ace, Rank::Ace, Rank::Ten]));
+        assert!(!table.get_dealer().is_soft());
+        assert_eq!(table.get_dealer().points, 12);
+        assert!(table.dealer_should_hit());
+    }
+
+    #[tokio::test]
+    async fn bet_limits_and_balances() {
+        assert!(BetLimits::new(0, 10).is_err());
+        assert!(BetLimits::new(20, 10).is_err());
+
+        let limits = BetLimits::new(5, 10).unwrap();
+        assert!(limits.check(4).is_err());
+        assert!(limits.check(5).is_ok());
+        assert!(limits.check(10).is_ok());
+        assert!(limits.check(11).is_err());
+
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        game.limits = limits;
+        let game_id = game.get_id().to_owned();
+
+        let alice = Arc::new(Player::new_without_id("Alice".to_string()));
+        let alice_id = alice.get_id();
+        manager.add_player(alice.clone()).unwrap();
+        manager.credit_balance(&alice_id, 12).unwrap();
+
+        assert_eq!(
+            manager.place_bet(&game_id, &alice_id, 5),
+            Err("Player didnt join the game.")
+        );
+
+        manager.player_join(&game_id, alice).unwrap();
+        manager.place_bet(&game_id, &alice_id, 8).unwrap();
+        assert_eq!(manager.get_balance(&alice_id), 4);
+
+        // the previous bet comes back before the new one is taken
+        manager.place_bet(&game_id, &alice_id, 10).unwrap();
+        assert_eq!(manager.get_balance(&alice_id), 2);
+        assert_eq!(
+            manager.place_bet(&game_id, &alice_id, 13),
+            Err("Bet above the game maximum.")
+        );
+        assert_eq!(
+            manager.debit_balance(&alice_id, 3),
+            Err("Insufficient balance.")
+        );
+        assert_eq!(manager.get_balance(&alice_id), 2);
+    }
+
+    #[tokio::test]
+    async fn payouts_at_settlement() {
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        let game_id = game.get_id().to_owned();
+
+        let mut ids = vec![];
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player.clone()).unwrap();
+            manager.credit_balance(&player.get_id(), 100).unwrap();
+            manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        let mut table = game.round_start(1, 0).unwrap();
+        let table_id = table.get_id().to_owned();
+
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Nine]));
+        table
+            .change_hand(&ids[0], cards(&[Rank::Ace, Rank::King]))
+            .unwrap();
+        table
+            .change_hand(&ids[1], cards(&[Rank::Five, Rank::Six, Rank::Ten]))
+            .unwrap();
+        table
+            .change_hand(&ids[2], cards(&[Rank::Ten, Rank::Nine]))
+            .unwrap();
+        table
+            .change_hand(&ids[3], cards(&[Rank::Ten, Rank::Eight]))
+            .unwrap();
+
+        manager.add_table(table);
+        manager.stop_game(&table_id).await.unwrap();
+
+        let balances = ids
+            .iter()
+            .map(|id| manager.get_balance(id))
+            .collect::<Vec<_>>();
+        assert_eq!(balances, [115, 110, 100, 90]);
+
+        let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+        let results = scoreboard["scoreboard"]["results"].as_array().unwrap();
+        let changes = results
+            .iter()
+            .map(|result| {
+                (
+                    result["result"].as_str().unwrap(),
+                    result["payout"].as_u64().unwrap(),
+                    result["balance_change"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            changes,
+            [
+                ("win", 25, 15),
+                ("win", 20, 10),
+                ("push", 10, 0),
+                ("lose", 0, -10)
+            ]
+        );
+
+        // bets are cleared with the players when the game goes back to the lobby
+        let game = manager.get_game_by_id(&game_id).unwrap();
+        assert!(game.players.is_empty());
+        assert_eq!(game.get_bet(&ids[0]), None);
+    }
+
+    #[tokio::test]
+    async fn late_surrender_refunds_half_the_bet() {
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        let game_id = game.get_id().to_owned();
+
+        let mut ids = vec![];
+        for name in ["Alice", "Bob"] {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player.clone()).unwrap();
+            manager.credit_balance(&player.get_id(), 100).unwrap();
+            manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        let mut table = game.round_start(1, 0).unwrap();
+        let table_id = table.get_id().to_owned();
+
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Nine]));
+        table
+            .change_hand(&ids[0], cards(&[Rank::Ten, Rank::Six]))
+            .unwrap();
+        table
+            .change_hand(&ids[1], cards(&[Rank::Two, Rank::Three, Rank::Four]))
+            .unwrap();
+
+        let result = table.surrender_player(&ids[1], 0);
+        assert_eq!(result, Err("Surrender only before the first hit."));
+
+        table.surrender_player(&ids[0], 0).unwrap();
+        let alice = table.get_player_by_id(&ids[0]).unwrap();
+        assert!(alice.is_surrendered());
+        assert!(alice.is_finished());
+        let hand = alice.generate_hand();
+        assert_eq!(hand["is_surrendered"], true);
+        assert_eq!(hand["is_standing"], false);
+        assert_eq!(hand["is_busted"], false);
+
+        assert_eq!(table.surrender_player(&ids[0], 0), Err("Already standing."));
+        let result = table.hit_player(&ids[0], 0, "blackjack").await;
+        assert_eq!(result, Err("Player surrendered."));
+
+        assert!(table.any_player_can_hit());
+        table.stand_player(&ids[1], 0).unwrap();
+        assert!(!table.any_player_can_hit());
+
+        manager.add_table(table);
+        manager.stop_game(&table_id).await.unwrap();
+        assert_eq!(manager.get_balance(&ids[0]), 95);
+        assert_eq!(manager.get_balance(&ids[1]), 90);
+
+        let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+        assert_eq!(scoreboard["scoreboard"]["surrendered"], json!(["Alice"]));
+        let results = scoreboard["scoreboard"]["results"].as_array().unwrap();
+        assert_eq!(results[0]["result"], "surrender");
+        assert_eq!(results[0]["payout"], 5);
+        assert_eq!(results[0]["balance_change"], -5);
+    }
+
+    #[tokio::test]
+    async fn naturals_settled_at_the_dealer_peek() {
+        let mut manager = Manager::new_with_games(1);
+        let game_id = manager.first_game_available().unwrap().get_id().to_owned();
+
+        let mut ids = vec![];
+        for name in ["Alice", "Bob"] {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player.clone()).unwrap();
+            manager.credit_balance(&player.get_id(), 100).unwrap();
+            manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        let mut table = game.round_start(1, 0).unwrap();
+        let table_id = table.get_id().to_owned();
+
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Seven]));
+        table
+            .change_hand(&ids[0], cards(&[Rank::Ace, Rank::King]))
+            .unwrap();
+        table
+            .change_hand(&ids[1], cards(&[Rank::Ten, Rank::Six]))
+            .unwrap();
+        manager.add_table(table);
+
+        // Alice is paid 3:2 right away, while Bob is still playing
+        manager.settle_naturals(&table_id).unwrap();
+        manager.settle_naturals(&table_id).unwrap();
+        assert_eq!(manager.get_balance(&ids[0]), 115);
+        assert_eq!(manager.get_balance(&ids[1]), 90);
+
+        let table = manager.get_table_mut(&table_id).unwrap();
+        assert!(table.any_player_can_hit());
+        let hands = table.generate_hands();
+        assert_eq!(hands["players"][0]["is_settled"], true);
+        assert_eq!(hands["players"][1]["is_settled"], false);
+
+        // the final settlement lists the natural without paying it twice
+        table.stand_player(&ids[1], 0).unwrap();
+        manager.stop_game(&table_id).await.unwrap();
+        assert_eq!(manager.get_balance(&ids[0]), 115);
+        assert_eq!(manager.get_balance(&ids[1]), 90);
+
+        let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+        let results = scoreboard["scoreboard"]["results"].as_array().unwrap();
+        assert_eq!(results[0]["result"], "win");
+        assert_eq!(results[0]["payout"], 25);
+        assert_eq!(results[0]["balance"], 115);
+        assert_eq!(results[1]["result"], "lose");
+    }
+
+    #[tokio::test]
+    async fn naturals_push_against_a_dealer_natural() {
+        let (mut table, ids) = table_with_players(&["Alice", "Bob"]);
+        table.change_dealer_hand(cards(&[Rank::Ace, Rank::Queen]));
+        table
+            .change_hand(&ids[0], cards(&[Rank::Ace, Rank::King]))
+            .unwrap();
+
+        // the dealer's natural ends the round, nothing is paid at the peek
+        assert!(table.settle_naturals().is_empty());
+        let settlements = table.settle();
+        assert_eq!(settlements[0].outcome, Outcome::Push);
+        assert!(!settlements[0].is_paid);
+    }
+
+    #[tokio::test]
+    async fn natural_ranks_above_multi_card_21() {
+        let cases = [
+            (
+                &[Rank::King, Rank::Six, Rank::Five][..],
+                [("win", 25), ("push", 10), ("lose", 0)],
+                Some("Alice"),
+            ),
+            (
+                &[Rank::Ace, Rank::Queen][..],
+                [("push", 10), ("lose", 0), ("lose", 0)],
+                None,
+            ),
+        ];
+
+        for (dealer, results, winner) in cases {
+            let mut manager = Manager::new_with_games(1);
+            let game = manager.first_game_available().unwrap();
+            let game_id = game.get_id().to_owned();
+
+            let mut ids = vec![];
+            for name in ["Alice", "Bob", "Carol"] {
+                let player = Arc::new(Player::new_without_id(name.to_string()));
+                ids.push(player.get_id());
+                manager.add_player(player.clone()).unwrap();
+                manager.player_join(&game_id, player.clone()).unwrap();
+                manager.credit_balance(&player.get_id(), 100).unwrap();
+                manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+            }
+
+            let game = manager.drop_game(&game_id).unwrap();
+            let mut table = game.round_start(1, 0).unwrap();
+            let table_id = table.get_id().to_owned();
+
+            table.change_dealer_hand(cards(dealer));
+            assert_eq!(table.dealer_has_blackjack(), dealer.len() == 2);
+
+            table
+                .change_hand(&ids[0], cards(&[Rank::Ace, Rank::Jack]))
+                .unwrap();
+            table
+                .change_hand(&ids[1], cards(&[Rank::Seven, Rank::Seven, Rank::Seven]))
+                .unwrap();
+            table
+                .change_hand(&ids[2], cards(&[Rank::Ten, Rank::Nine]))
+                .unwrap();
+
+            assert!(table.get_player_by_id(&ids[0]).unwrap().is_blackjack());
+            assert!(!table.get_player_by_id(&ids[1]).unwrap().is_blackjack());
+            let hands = table.generate_final_hands();
+            assert_eq!(hands["players"][0]["is_blackjack"], true);
+            assert_eq!(hands["players"][1]["is_blackjack"], false);
+
+            // Alice's natural beats Bob's three card 21, but only pushes against the dealer's
+            let best = table.get_winner().await;
+            assert_eq!(best.map(|player| player.name.clone()).as_deref(), winner);
+
+            manager.add_table(table);
+            manager.stop_game(&table_id).await.unwrap();
+
+            let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+            let settled = scoreboard["scoreboard"]["results"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|result| {
+                    (
+                        result["result"].as_str().unwrap(),
+                        result["payout"].as_u64().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(settled, results);
+        }
+    }
+
+    #[tokio::test]
+    async fn scoreboard_ranks_every_player() {
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        let game_id = game.get_id().to_owned();
+
+        let names = ["Alice", "Bob", "Carol", "Dave", "Eve", "Frank", "Grace"];
+        let mut ids = vec![];
+        for name in names {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player.clone()).unwrap();
+            manager.credit_balance(&player.get_id(), 100).unwrap();
+            manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        let mut table = game.round_start(1, 0).unwrap();
+        let table_id = table.get_id().to_owned();
+
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Nine]));
+        for (id, ranks) in ids.iter().zip([
+            &[Rank::King, Rank::Queen][..],
+            &[Rank::Seven, Rank::Seven, Rank::Seven][..],
+            &[Rank::Ten, Rank::Nine][..],
+            &[Rank::Jack, Rank::Ten][..],
+            &[Rank::Ten, Rank::Six, Rank::Eight][..],
+            &[Rank::Ace, Rank::King][..],
+            &[Rank::Ten, Rank::Six][..],
+        ]) {
+            table.change_hand(id, cards(ranks)).unwrap();
+        }
+        table.surrender_player(&ids[6], 0).unwrap();
+
+        // A tie ahead of a better hand doesn't hide it
+        assert_eq!(table.get_winner().await.unwrap().name, "Frank");
+
+        manager.add_table(table);
+        manager.stop_game(&table_id).await.unwrap();
+
+        let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+        assert_eq!(scoreboard["scoreboard"]["winner"], "Frank");
+
+        let ranking = scoreboard["scoreboard"]["ranking"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rank| {
+                (
+                    rank["position"].as_u64().unwrap(),
+                    rank["name"].as_str().unwrap(),
+                    rank["result"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranking,
+            [
+                (1, "Frank", "win"),
+                (2, "Bob", "win"),
+                (3, "Alice", "win"),
+                (3, "Dave", "win"),
+                (5, "Carol", "push"),
+                (6, "Eve", "bust"),
+                (6, "Grace", "surrender"),
+            ]
+        );
+
+        let results = scoreboard["scoreboard"]["results"].as_array().unwrap();
+        assert_eq!(results.len(), names.len());
+        let points = results
+            .iter()
+            .map(|result| {
+                (
+                    result["points"].as_u64().unwrap(),
+                    result["payout"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            points,
+            [
+                (20, 20),
+                (21, 20),
+                (19, 10),
+                (20, 20),
+                (24, 0),
+                (21, 25),
+                (16, 5)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn no_winner_when_every_player_loses_to_the_dealer() {
+        let mut manager = Manager::new_with_games(1);
+        let game = manager.first_game_available().unwrap();
+        let game_id = game.get_id().to_owned();
+
+        let mut ids = vec![];
+        for name in ["Alice", "Bob", "Carol"] {
+            let player = Arc::new(Player::new_without_id(name.to_string()));
+            ids.push(player.get_id());
+            manager.add_player(player.clone()).unwrap();
+            manager.player_join(&game_id, player.clone()).unwrap();
+            manager.credit_balance(&player.get_id(), 100).unwrap();
+            manager.place_bet(&game_id, &player.get_id(), 10).unwrap();
+        }
+
+        let game = manager.drop_game(&game_id).unwrap();
+        let mut table = game.round_start(1, 0).unwrap();
+        let table_id = table.get_id().to_owned();
+
+        table.change_dealer_hand(cards(&[Rank::King, Rank::Queen]));
+        for (id, ranks) in ids.iter().zip([
+            &[Rank::Ten, Rank::Nine][..],
+            &[Rank::Ten, Rank::Eight][..],
+            &[Rank::Ten, Rank::Six, Rank::Eight][..],
+        ]) {
+            table.change_hand(id, cards(ranks)).unwrap();
+        }
+
+        // Alice has the best hand, yet it lost to the dealer's 20
+        assert!(table.get_winner().await.is_none());
+
+        manager.add_table(table);
+        manager.stop_game(&table_id).await.unwrap();
+
+        let scoreboard = manager.get_scoreboard(&table_id).unwrap().to_json();
+        assert_eq!(scoreboard["scoreboard"]["winner"], "DRAW");
+
+        let ranking = scoreboard["scoreboard"]["ranking"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rank| {
+                (
+                    rank["position"].as_u64().unwrap(),
+                    rank["name"].as_str().unwrap(),
+                    rank["result"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranking,
+            [
+                (1, "Alice", "lose"),
+                (2, "Bob", "lose"),
+                (3, "Carol", "bust")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_winner_after_a_tie() {
+        let (mut table, ids) = table_with_players(&["Alice", "Bob", "Carol"]);
+
+        table.change_points(&ids[0], 20).unwrap();
+        table.change_points(&ids[1], 20).unwrap();
+        table.change_points(&ids[2], 19).unwrap();
+        assert!(table.get_winner().await.is_none());
+
+        table.change_points(&ids[2], 21).unwrap();
+        assert_eq!(table.get_winner().await.unwrap().name, "Carol");
+    }
 }
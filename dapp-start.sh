@@ -29,6 +29,8 @@ fi
 # mkdir -p data/address data/names
 
 export RUST_LOG=info
+export RUN_MODE=production
 export ADDRESS_OWNER_GAME=0x70997970C51812dc3A010C7d01b50e0d17dc79C8
+export DRAND_CONFIG_OWNER=$ADDRESS_OWNER_GAME
 ./cartesi-drand &
 ./dapp-contract-blackjack